@@ -0,0 +1,297 @@
+//! The bit of JSON an `--input-lines-from-args-file` needs: an array of
+//! objects, each a line's `text` with the `fill` and `size` it overrides.
+//!
+//! ```
+//! use sentences2svg::{args_file, render_line, RenderOptions};
+//!
+//! let lines = args_file::parse(
+//!     r##"[{"text": "Title", "fill": "#c00", "size": 48},
+//!         {"text": "Title", "fill": "blue", "size": 12},
+//!         {"text": "body text"}]"##,
+//! )
+//! .unwrap();
+//! assert_eq!(lines[0].fill.as_deref(), Some("#c00"));
+//! assert_eq!(lines[1].size, Some(12.0));
+//! assert_eq!((&lines[2].fill, lines[2].size), (&None, None));
+//!
+//! let font = std::fs::read("fonts/DejaVuSans.ttf").unwrap();
+//! let faces = [ttf_parser::Face::from_slice(&font, 0).unwrap()];
+//! let svgs: Vec<_> = lines[..2]
+//!     .iter()
+//!     .map(|line| {
+//!         let options = RenderOptions {
+//!             fill: line.fill.clone(),
+//!             size: line.size,
+//!             ..RenderOptions::default()
+//!         };
+//!         render_line(&faces, &line.text, &options).unwrap()
+//!     })
+//!     .collect();
+//! assert!(svgs[0].contains("fill='#c00'"));
+//! assert!(svgs[1].contains("fill='blue'"));
+//! // Four times the size, four times as tall.
+//! let height = |svg: &str| {
+//!     let start = svg.find("height='").unwrap() + 8;
+//!     let end = start + svg[start..].find('\'').unwrap();
+//!     svg[start..end].parse::<f32>().unwrap()
+//! };
+//! assert!((height(&svgs[0]) - 4.0 * height(&svgs[1])).abs() < 0.01);
+//! ```
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub text: String,
+    /// `None` where the object leaves it out or gives `null`.
+    pub fill: Option<String>,
+    pub size: Option<f32>,
+}
+
+#[derive(Debug)]
+enum Value {
+    Null,
+    Text(String),
+    Number(f64),
+    /// Booleans, arrays and objects, which no key takes.
+    Other,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    /// One based line of where the parser is, for errors.
+    fn line(&self) -> usize {
+        self.text[..self.position].matches('\n').count() + 1
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line(), message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        let trimmed = rest.trim_start_matches([' ', '\t', '\n', '\r']);
+        self.position += rest.len() - trimmed.len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", c))
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self.rest().get(..4).unwrap_or("");
+        match u32::from_str_radix(digits, 16) {
+            Ok(n) if digits.len() == 4 => {
+                self.position += 4;
+                Ok(n)
+            }
+            _ => self.error("\\u needs four hex digits"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let c = match self.rest().chars().next() {
+                Some(c) => c,
+                None => return self.error("string is never closed"),
+            };
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escape = self.rest().chars().next();
+                    self.position += escape.map_or(0, char::len_utf8);
+                    out.push(match escape {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut n = self.hex()?;
+                            if (0xD800..0xDC00).contains(&n)
+                                && self.rest().starts_with("\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                n = 0x10000
+                                    + ((n - 0xD800) << 10)
+                                    + low.wrapping_sub(0xDC00);
+                            }
+                            match char::from_u32(n) {
+                                Some(c) => c,
+                                None => {
+                                    return self.error("\\u isn't a character")
+                                }
+                            }
+                        }
+                        _ => return self.error("unknown escape in a string"),
+                    });
+                }
+                c if c < ' ' => {
+                    return self.error("control character in a string")
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// Reads any value, keeping only what a line's keys can be.
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::Text),
+            Some('[') => {
+                self.skip('[', ']')?;
+                Ok(Value::Other)
+            }
+            Some('{') => {
+                self.skip('{', '}')?;
+                Ok(Value::Other)
+            }
+            Some(_) => {
+                let rest = self.rest();
+                let end = rest
+                    .find(|c: char| {
+                        !(c.is_ascii_alphanumeric() || "+-.".contains(c))
+                    })
+                    .unwrap_or(rest.len());
+                let word = &rest[..end];
+                let value = match word {
+                    "null" => Value::Null,
+                    "true" | "false" => Value::Other,
+                    _ => match word.parse::<f64>() {
+                        Ok(n) if n.is_finite() => Value::Number(n),
+                        _ => return self.error("expected a value"),
+                    },
+                };
+                self.position += end;
+                Ok(value)
+            }
+            None => self.error("expected a value"),
+        }
+    }
+
+    /// Steps over an array or object whatever is in it.
+    fn skip(&mut self, open: char, close: char) -> Result<(), String> {
+        self.expect(open)?;
+        if self.peek() == Some(close) {
+            self.position += 1;
+            return Ok(());
+        }
+        loop {
+            if open == '{' {
+                self.string()?;
+                self.expect(':')?;
+            }
+            self.value()?;
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(c) if c == close => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                _ => {
+                    return self.error(&format!("expected `,` or `{}`", close))
+                }
+            }
+        }
+    }
+
+    fn line_object(&mut self) -> Result<Line, String> {
+        self.expect('{')?;
+        let start = self.line();
+        let (mut text, mut fill, mut size) = (None, None, None);
+        let mut seen = vec![];
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Err(format!("line {}: a line needs a text", start));
+        }
+        loop {
+            let key = self.string()?;
+            if seen.contains(&key) {
+                return self.error(&format!("{} is given twice", key));
+            }
+            self.expect(':')?;
+            let value = self.value()?;
+            match (key.as_str(), value) {
+                ("fill" | "size", Value::Null) => {}
+                ("text", Value::Text(s)) => text = Some(s),
+                ("fill", Value::Text(s)) => fill = Some(s),
+                ("size", Value::Number(n)) => size = Some(n as f32),
+                ("text" | "fill", _) => {
+                    return self.error(&format!("{} must be a string", key))
+                }
+                ("size", _) => return self.error("size must be a number"),
+                _ => {
+                    return self.error(&format!(
+                        "{} isn't a key, only text, fill and size are",
+                        key
+                    ))
+                }
+            }
+            seen.push(key);
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    break;
+                }
+                _ => return self.error("expected `,` or `}`"),
+            }
+        }
+        match text {
+            Some(text) => Ok(Line { text, fill, size }),
+            None => Err(format!("line {}: a line needs a text", start)),
+        }
+    }
+}
+
+/// Reads the array of lines in `text`.
+pub fn parse(text: &str) -> Result<Vec<Line>, String> {
+    let mut parser = Parser { text, position: 0 };
+    if parser.rest().starts_with('\u{feff}') {
+        parser.position += 3;
+    }
+    let mut lines = vec![];
+    parser.expect('[')?;
+    if parser.peek() == Some(']') {
+        parser.position += 1;
+    } else {
+        loop {
+            lines.push(parser.line_object()?);
+            match parser.peek() {
+                Some(',') => parser.position += 1,
+                Some(']') => {
+                    parser.position += 1;
+                    break;
+                }
+                _ => return parser.error("expected `,` or `]`"),
+            }
+        }
+    }
+    if parser.peek().is_some() {
+        return parser.error("nothing may follow the array");
+    }
+    Ok(lines)
+}
@@ -0,0 +1,451 @@
+//! A cut down Unicode bidi algorithm, enough to put right to left runs
+//! and the numbers and Latin inside them in visual order. Explicit
+//! embeddings and isolates are left alone, the line is one paragraph.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+    /// From the first strongly directional character, left to right when
+    /// there is none.
+    Auto,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    L,
+    R,
+    /// Arabic letters, which turn the digits after them into `An`.
+    Al,
+    En,
+    An,
+    /// Separators inside a number, like the dot in 1.5.
+    Cs,
+    /// Signs next to a number, like % and currencies.
+    Et,
+    /// Combining marks, which take the class of what they're on.
+    Nsm,
+    Ws,
+    On,
+}
+
+fn class(c: char) -> Class {
+    use Class::*;
+    match c {
+        '0'..='9' | '\u{06F0}'..='\u{06F9}' => En,
+        '\u{0660}'..='\u{0669}' | '\u{066B}' | '\u{066C}' => An,
+        '.' | ',' | ':' | '/' | '+' | '-' => Cs,
+        '#'
+        | '$'
+        | '%'
+        | '\u{00B0}'
+        | '\u{00A2}'..='\u{00A5}'
+        | '\u{20A0}'..='\u{20CF}' => Et,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{05BF}'
+        | '\u{05C1}'..='\u{05C2}'
+        | '\u{05C4}'..='\u{05C5}'
+        | '\u{05C7}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0670}'
+        | '\u{06D6}'..='\u{06DC}'
+        | '\u{06DF}'..='\u{06E4}'
+        | '\u{06E7}'..='\u{06E8}'
+        | '\u{06EA}'..='\u{06ED}' => Nsm,
+        '\u{0590}'..='\u{05FF}'
+        | '\u{07C0}'..='\u{085F}'
+        | '\u{FB1D}'..='\u{FB4F}'
+        | '\u{10800}'..='\u{10FFF}' => R,
+        '\u{0600}'..='\u{07BF}'
+        | '\u{0860}'..='\u{08FF}'
+        | '\u{FB50}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{1EE00}'..='\u{1EEFF}' => Al,
+        c if c.is_whitespace() => Ws,
+        c if c.is_alphabetic() => L,
+        _ => On,
+    }
+}
+
+/// The bracket or sign pointing the other way, for right to left runs.
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '\u{00AB}' => '\u{00BB}',
+        '\u{00BB}' => '\u{00AB}',
+        '\u{2039}' => '\u{203A}',
+        '\u{203A}' => '\u{2039}',
+        '\u{2264}' => '\u{2265}',
+        '\u{2265}' => '\u{2264}',
+        c => c,
+    }
+}
+
+/// The direction of the text's first strongly directional character,
+/// `None` when it has none.
+///
+/// ```
+/// use sentences2svg::bidi::{detect, Direction};
+///
+/// let line = |text: &str| detect(&text.chars().collect::<Vec<_>>());
+/// assert_eq!(line("(shalom)"), Some(Direction::Ltr));
+/// let hebrew = "(\u{05E9}\u{05DC}\u{05D5}\u{05DD}) 42";
+/// assert_eq!(line(hebrew), Some(Direction::Rtl));
+/// assert_eq!(line("42 (!)"), None);
+/// ```
+pub fn detect(chars: &[char]) -> Option<Direction> {
+    let class = chars
+        .iter()
+        .map(|&c| class(c))
+        .find(|class| matches!(class, Class::L | Class::R | Class::Al))?;
+    Some(match class {
+        Class::L => Direction::Ltr,
+        _ => Direction::Rtl,
+    })
+}
+
+/// Bidi_Mirroring_Glyph pairs past the ones `mirror` knows: CJK,
+/// fullwidth and math brackets, and relations like subset of.
+const MIRRORED: &[(char, char)] = &[
+    ('\u{2045}', '\u{2046}'),
+    ('\u{207D}', '\u{207E}'),
+    ('\u{208D}', '\u{208E}'),
+    ('\u{2308}', '\u{2309}'),
+    ('\u{230A}', '\u{230B}'),
+    ('\u{2329}', '\u{232A}'),
+    ('\u{2768}', '\u{2769}'),
+    ('\u{276A}', '\u{276B}'),
+    ('\u{276C}', '\u{276D}'),
+    ('\u{276E}', '\u{276F}'),
+    ('\u{2770}', '\u{2771}'),
+    ('\u{2772}', '\u{2773}'),
+    ('\u{2774}', '\u{2775}'),
+    ('\u{27C5}', '\u{27C6}'),
+    ('\u{27E6}', '\u{27E7}'),
+    ('\u{27E8}', '\u{27E9}'),
+    ('\u{27EA}', '\u{27EB}'),
+    ('\u{27EC}', '\u{27ED}'),
+    ('\u{27EE}', '\u{27EF}'),
+    ('\u{2983}', '\u{2984}'),
+    ('\u{2985}', '\u{2986}'),
+    ('\u{2987}', '\u{2988}'),
+    ('\u{2989}', '\u{298A}'),
+    ('\u{298B}', '\u{298C}'),
+    ('\u{298D}', '\u{2990}'),
+    ('\u{298F}', '\u{298E}'),
+    ('\u{2991}', '\u{2992}'),
+    ('\u{2993}', '\u{2994}'),
+    ('\u{2995}', '\u{2996}'),
+    ('\u{2997}', '\u{2998}'),
+    ('\u{29D8}', '\u{29D9}'),
+    ('\u{29DA}', '\u{29DB}'),
+    ('\u{29FC}', '\u{29FD}'),
+    ('\u{2E02}', '\u{2E03}'),
+    ('\u{2E04}', '\u{2E05}'),
+    ('\u{2E09}', '\u{2E0A}'),
+    ('\u{2E0C}', '\u{2E0D}'),
+    ('\u{2E1C}', '\u{2E1D}'),
+    ('\u{2E20}', '\u{2E21}'),
+    ('\u{2E22}', '\u{2E23}'),
+    ('\u{2E24}', '\u{2E25}'),
+    ('\u{2E26}', '\u{2E27}'),
+    ('\u{2E28}', '\u{2E29}'),
+    ('\u{3008}', '\u{3009}'),
+    ('\u{300A}', '\u{300B}'),
+    ('\u{300C}', '\u{300D}'),
+    ('\u{300E}', '\u{300F}'),
+    ('\u{3010}', '\u{3011}'),
+    ('\u{3014}', '\u{3015}'),
+    ('\u{3016}', '\u{3017}'),
+    ('\u{3018}', '\u{3019}'),
+    ('\u{301A}', '\u{301B}'),
+    ('\u{FE59}', '\u{FE5A}'),
+    ('\u{FE5B}', '\u{FE5C}'),
+    ('\u{FE5D}', '\u{FE5E}'),
+    ('\u{FE64}', '\u{FE65}'),
+    ('\u{FF08}', '\u{FF09}'),
+    ('\u{FF1C}', '\u{FF1E}'),
+    ('\u{FF3B}', '\u{FF3D}'),
+    ('\u{FF5B}', '\u{FF5D}'),
+    ('\u{FF5F}', '\u{FF60}'),
+    ('\u{FF62}', '\u{FF63}'),
+    ('\u{2208}', '\u{220B}'),
+    ('\u{2209}', '\u{220C}'),
+    ('\u{220A}', '\u{220D}'),
+    ('\u{2243}', '\u{22CD}'),
+    ('\u{2252}', '\u{2253}'),
+    ('\u{2254}', '\u{2255}'),
+    ('\u{2266}', '\u{2267}'),
+    ('\u{2268}', '\u{2269}'),
+    ('\u{226A}', '\u{226B}'),
+    ('\u{226E}', '\u{226F}'),
+    ('\u{2270}', '\u{2271}'),
+    ('\u{2272}', '\u{2273}'),
+    ('\u{2276}', '\u{2277}'),
+    ('\u{227A}', '\u{227B}'),
+    ('\u{227C}', '\u{227D}'),
+    ('\u{2282}', '\u{2283}'),
+    ('\u{2284}', '\u{2285}'),
+    ('\u{2286}', '\u{2287}'),
+    ('\u{2288}', '\u{2289}'),
+    ('\u{228A}', '\u{228B}'),
+    ('\u{228F}', '\u{2290}'),
+    ('\u{2291}', '\u{2292}'),
+    ('\u{22A2}', '\u{22A3}'),
+    ('\u{22B2}', '\u{22B3}'),
+    ('\u{22B4}', '\u{22B5}'),
+    ('\u{22D0}', '\u{22D1}'),
+    ('\u{22D6}', '\u{22D7}'),
+    ('\u{22DA}', '\u{22DB}'),
+];
+
+/// `mirror`, or else the character the Unicode tables pair it with.
+fn mirror_all(c: char) -> char {
+    let pair = MIRRORED.iter().find_map(|&(a, b)| {
+        if c == a {
+            Some(b)
+        } else if c == b {
+            Some(a)
+        } else {
+            None
+        }
+    });
+    pair.unwrap_or_else(|| mirror(c))
+}
+
+/// Whether the text starts out right to left, going by its first strongly
+/// directional character.
+pub fn is_rtl(chars: &[char], direction: Direction) -> bool {
+    match direction {
+        Direction::Ltr => false,
+        Direction::Rtl => true,
+        Direction::Auto => detect(chars) == Some(Direction::Rtl),
+    }
+}
+
+/// Puts the characters in the order they're drawn left to right, with
+/// brackets in right to left runs mirrored, every Bidi_Mirrored pair with
+/// `every_pair`. Combining marks stay after the character they belong to.
+///
+/// ```
+/// use sentences2svg::bidi::{visual, Direction};
+///
+/// let text = "\u{05D0}(\u{05D1}) \u{300C}\u{05D2}\u{300D}";
+/// let drawn = |every_pair| -> String {
+///     visual(text.chars().collect(), Direction::Auto, every_pair)
+///         .into_iter()
+///         .collect()
+/// };
+/// // The corner brackets are only mirrored along with every pair.
+/// let basic = "\u{300D}\u{05D2}\u{300C} (\u{05D1})\u{05D0}";
+/// assert_eq!(drawn(false), basic);
+/// let every = "\u{300C}\u{05D2}\u{300D} (\u{05D1})\u{05D0}";
+/// assert_eq!(drawn(true), every);
+/// ```
+pub fn visual(
+    chars: Vec<char>,
+    direction: Direction,
+    every_pair: bool,
+) -> Vec<char> {
+    use Class::*;
+    let paragraph = if is_rtl(&chars, direction) { 1 } else { 0 };
+    let outer = if paragraph == 1 { R } else { L };
+    let original = chars.iter().map(|&c| class(c)).collect::<Vec<_>>();
+    if paragraph == 0 && !original.iter().any(|&c| matches!(c, R | Al | An)) {
+        return chars;
+    }
+    let mut classes = original.clone();
+
+    // W1: marks take the class of what they're on.
+    for i in 0..classes.len() {
+        if classes[i] == Nsm {
+            classes[i] = if i == 0 { outer } else { classes[i - 1] };
+        }
+    }
+    // W2 and W3: digits after Arabic letters are Arabic numbers, which
+    // letters are right to left like any other.
+    let mut last_strong = outer;
+    for class in classes.iter_mut() {
+        match *class {
+            L | R | Al => last_strong = *class,
+            En if last_strong == Al => *class = An,
+            _ => {}
+        }
+        if *class == Al {
+            *class = R;
+        }
+    }
+    // W4: a single separator between two numbers of a kind joins them.
+    for i in 1..classes.len().saturating_sub(1) {
+        if classes[i] == Cs
+            && classes[i - 1] == classes[i + 1]
+            && matches!(classes[i - 1], En | An)
+        {
+            classes[i] = classes[i - 1];
+        }
+    }
+    // W5: signs next to European numbers are part of them.
+    for i in 0..classes.len() {
+        if classes[i] != En {
+            continue;
+        }
+        for j in (0..i).rev() {
+            if classes[j] != Et {
+                break;
+            }
+            classes[j] = En;
+        }
+        for class in classes[i + 1..].iter_mut() {
+            if *class != Et {
+                break;
+            }
+            *class = En;
+        }
+    }
+    // W6: whatever is left over is neutral.
+    for class in classes.iter_mut() {
+        if matches!(*class, Cs | Et) {
+            *class = On;
+        }
+    }
+    // W7: European numbers in left to right text are left to right.
+    let mut last_strong = outer;
+    for class in classes.iter_mut() {
+        match *class {
+            L | R => last_strong = *class,
+            En if last_strong == L => *class = L,
+            _ => {}
+        }
+    }
+    let strong = |class: Class| match class {
+        L => Some(L),
+        R | En | An => Some(R),
+        _ => None,
+    };
+    // N0: a bracket pair goes the paragraph's way if anything inside does,
+    // otherwise the way of what's before it, as long as it holds anything
+    // directional at all.
+    let mut open: Vec<(usize, char)> = vec![];
+    let mut pairs = vec![];
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '(' | '[' | '{' if classes[i] == On => open.push((i, c)),
+            ')' | ']' | '}' if classes[i] == On => {
+                if let Some(at) =
+                    open.iter().rposition(|&(_, o)| mirror(o) == c)
+                {
+                    pairs.push((open[at].0, i));
+                    open.truncate(at);
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs.sort_unstable();
+    for (start, end) in pairs {
+        let inside = classes[start + 1..end]
+            .iter()
+            .filter_map(|&class| strong(class))
+            .collect::<Vec<_>>();
+        let resolved = if inside.contains(&outer) {
+            outer
+        } else if inside.is_empty() {
+            continue;
+        } else {
+            classes[..start]
+                .iter()
+                .rev()
+                .find_map(|&class| strong(class))
+                .unwrap_or(outer)
+        };
+        classes[start] = resolved;
+        classes[end] = resolved;
+    }
+    // N1 and N2: neutrals between the same direction go that way, numbers
+    // counting as right to left, and the paragraph's way otherwise.
+    let mut i = 0;
+    while i < classes.len() {
+        if strong(classes[i]).is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && strong(classes[i]).is_none() {
+            i += 1;
+        }
+        let before = match start {
+            0 => outer,
+            _ => strong(classes[start - 1]).unwrap(),
+        };
+        let after = classes.get(i).map_or(outer, |&c| strong(c).unwrap());
+        let resolved = if before == after { before } else { outer };
+        for class in &mut classes[start..i] {
+            *class = resolved;
+        }
+    }
+    // I1 and I2.
+    let mut levels = classes
+        .iter()
+        .map(|&class| match (paragraph, class) {
+            (0, R) => 1,
+            (0, En) | (0, An) => 2,
+            (1, L) | (1, En) | (1, An) => 2,
+            _ => paragraph,
+        })
+        .collect::<Vec<u8>>();
+    // L1: trailing whitespace goes back to the paragraph's level.
+    for (level, class) in levels.iter_mut().zip(&original).rev() {
+        if *class != Ws {
+            break;
+        }
+        *level = paragraph;
+    }
+
+    // L2, reversing a character together with its marks.
+    let mut clusters: Vec<(u8, Vec<char>)> = vec![];
+    for (i, &c) in chars.iter().enumerate() {
+        match clusters.last_mut() {
+            Some((_, cluster)) if original[i] == Nsm => cluster.push(c),
+            _ => clusters.push((levels[i], vec![c])),
+        }
+    }
+    let highest = clusters.iter().map(|(level, _)| *level).max().unwrap_or(0);
+    for level in (1..=highest).rev() {
+        let mut i = 0;
+        while i < clusters.len() {
+            if clusters[i].0 < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < clusters.len() && clusters[i].0 >= level {
+                i += 1;
+            }
+            clusters[start..i].reverse();
+        }
+    }
+    // L4.
+    clusters
+        .into_iter()
+        .flat_map(|(level, cluster)| {
+            cluster.into_iter().map(move |c| {
+                if level % 2 == 1 && every_pair {
+                    mirror_all(c)
+                } else if level % 2 == 1 {
+                    mirror(c)
+                } else {
+                    c
+                }
+            })
+        })
+        .collect()
+}
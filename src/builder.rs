@@ -0,0 +1,264 @@
+use std::fmt::Write;
+
+pub struct Builder {
+    pub buffer: String,
+    pub offset: f32,
+    pub baseline_offset: f32,
+    pub scale: f32,
+    /// Converts the glyph's font units to the primary face's.
+    pub units: f32,
+    /// Snap every point to whole pixels.
+    pub round: bool,
+    /// Decimal places to round coordinates to, if any.
+    pub precision: Option<i32>,
+    /// Write relative commands with as few separators as possible.
+    pub compact: Option<Compact>,
+    /// Write curves as lines.
+    pub flatten: Option<Flatten>,
+    /// Leave the x coordinates of absolute commands out, noting where each
+    /// goes and what it is in font units, for `fill` to put back at any
+    /// offset.
+    pub holes: Option<Vec<(usize, f32)>>,
+}
+
+/// Path data recorded with `Builder::holes`.
+pub struct Holed {
+    pub data: String,
+    pub holes: Vec<(usize, f32)>,
+}
+
+/// Splits curves into enough lines to stay within the tolerance of them.
+pub struct Flatten {
+    /// In output units, like the path data.
+    tolerance: f32,
+    /// In font units, before any scaling.
+    current: (f32, f32),
+    start: (f32, f32),
+}
+
+impl Flatten {
+    pub fn new(tolerance: f32) -> Self {
+        Flatten {
+            tolerance,
+            current: (0.0, 0.0),
+            start: (0.0, 0.0),
+        }
+    }
+
+    /// Points along the curve through the control points, the current
+    /// point being the first. Wang's formula gives how many lines keep
+    /// within the tolerance, from the largest second difference.
+    fn points(&self, controls: &[(f32, f32)], scale: f32) -> Vec<(f32, f32)> {
+        let mut points = vec![self.current];
+        points.extend_from_slice(controls);
+        let degree = controls.len() as f32;
+        let second_difference = points
+            .windows(3)
+            .map(|p| {
+                let x = p[0].0 - 2.0 * p[1].0 + p[2].0;
+                let y = p[0].1 - 2.0 * p[1].1 + p[2].1;
+                (x * x + y * y).sqrt() * scale
+            })
+            .fold(0.0f32, f32::max);
+        let lines = (degree * (degree - 1.0) / 8.0 * second_difference
+            / self.tolerance)
+            .sqrt()
+            .ceil()
+            .max(1.0) as usize;
+        (1..=lines)
+            .map(|i| {
+                let t = i as f32 / lines as f32;
+                // De Casteljau, down to the one point at t.
+                let mut level = points.clone();
+                while level.len() > 1 {
+                    level = level
+                        .windows(2)
+                        .map(|p| {
+                            (
+                                p[0].0 + (p[1].0 - p[0].0) * t,
+                                p[0].1 + (p[1].1 - p[0].1) * t,
+                            )
+                        })
+                        .collect();
+                }
+                level[0]
+            })
+            .collect()
+    }
+}
+
+/// Where the pen is, for writing the next command relative to it.
+#[derive(Default)]
+pub struct Compact {
+    current: (f32, f32),
+    /// Start of the contour, where closing it returns to.
+    start: (f32, f32),
+    /// Commands after the first of a kind leave their letter out.
+    last: Option<char>,
+}
+
+impl Builder {
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x(x), self.y(y))
+    }
+
+    fn x(&self, x: f32) -> f32 {
+        self.tidy((x * self.units + self.offset) * self.scale)
+    }
+
+    fn y(&self, y: f32) -> f32 {
+        self.tidy(self.baseline_offset - y * self.units * self.scale)
+    }
+
+    fn tidy(&self, n: f32) -> f32 {
+        if self.round {
+            n.round()
+        } else if let Some(precision) = self.precision {
+            let factor = 10f32.powi(precision);
+            // Adding zero turns the -0 small negatives round to into 0.
+            (n * factor).round() / factor + 0.0
+        } else {
+            n
+        }
+    }
+
+    /// Writes an absolute command, or its letter and the points' y
+    /// coordinates with holes for the x ones.
+    fn write_absolute(&mut self, command: char, points: &[(f32, f32)]) {
+        write!(&mut self.buffer, "{} ", command).unwrap();
+        for &(x, y) in points {
+            let (at, y) = (self.buffer.len(), self.y(y));
+            match &mut self.holes {
+                Some(holes) => holes.push((at, x)),
+                None => {
+                    let x = self.x(x);
+                    write!(&mut self.buffer, "{}", x).unwrap()
+                }
+            }
+            write!(&mut self.buffer, " {} ", y).unwrap();
+        }
+    }
+
+    /// Writes path data recorded with holes, the x coordinates filled in
+    /// at this builder's offset.
+    pub fn fill(&mut self, path: &Holed) {
+        let mut written = 0;
+        for &(at, x) in &path.holes {
+            let x = self.x(x);
+            self.buffer.push_str(&path.data[written..at]);
+            write!(&mut self.buffer, "{}", x).unwrap();
+            written = at;
+        }
+        self.buffer.push_str(&path.data[written..]);
+    }
+
+    /// Writes the points relative to the current one. Returns false
+    /// without writing anything unless the path is compact.
+    fn write_compact(&mut self, command: char, points: &[(f32, f32)]) -> bool {
+        let (precision, round) = (self.precision, self.round);
+        let compact = match &mut self.compact {
+            Some(compact) => compact,
+            None => return false,
+        };
+        // The differences of rounded points are rounded again, so float
+        // error doesn't bring back the digits that were rounded away.
+        let tidy = |d: f32| match precision {
+            _ if round => d.round() + 0.0,
+            Some(precision) => {
+                let factor = 10f32.powi(precision);
+                (d * factor).round() / factor + 0.0
+            }
+            None => d,
+        };
+        // A repeated moveto would be read as a lineto.
+        let mut separate = if compact.last == Some(command) && command != 'm' {
+            !self.buffer.is_empty()
+        } else {
+            self.buffer.push(command);
+            false
+        };
+        let (x0, y0) = compact.current;
+        for &(x, y) in points {
+            for d in [tidy(x - x0), tidy(y - y0)] {
+                if separate && d >= 0.0 {
+                    self.buffer.push(' ');
+                }
+                write!(&mut self.buffer, "{}", d).unwrap();
+                separate = true;
+            }
+        }
+        let &(x, y) = points.last().unwrap();
+        compact.current = (x0 + tidy(x - x0), y0 + tidy(y - y0));
+        if command == 'm' {
+            compact.start = compact.current;
+        }
+        compact.last = Some(command);
+        true
+    }
+}
+
+impl ttf_parser::OutlineBuilder for Builder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if let Some(flatten) = &mut self.flatten {
+            flatten.current = (x, y);
+            flatten.start = (x, y);
+        }
+        let point = self.point(x, y);
+        if !self.write_compact('m', &[point]) {
+            self.write_absolute('M', &[(x, y)]);
+        }
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        if let Some(flatten) = &mut self.flatten {
+            flatten.current = (x, y);
+        }
+        let point = self.point(x, y);
+        if !self.write_compact('l', &[point]) {
+            self.write_absolute('L', &[(x, y)]);
+        }
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        if let Some(flatten) = &self.flatten {
+            let scale = self.units * self.scale;
+            for (x, y) in flatten.points(&[(x1, y1), (x, y)], scale) {
+                self.line_to(x, y);
+            }
+            return;
+        }
+        let points = [self.point(x1, y1), self.point(x, y)];
+        if !self.write_compact('q', &points) {
+            self.write_absolute('Q', &[(x1, y1), (x, y)]);
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        if let Some(flatten) = &self.flatten {
+            let scale = self.units * self.scale;
+            let controls = [(x1, y1), (x2, y2), (x, y)];
+            for (x, y) in flatten.points(&controls, scale) {
+                self.line_to(x, y);
+            }
+            return;
+        }
+        let points = [self.point(x1, y1), self.point(x2, y2), self.point(x, y)];
+        if !self.write_compact('c', &points) {
+            self.write_absolute('C', &[(x1, y1), (x2, y2), (x, y)]);
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(flatten) = &mut self.flatten {
+            flatten.current = flatten.start;
+        }
+        match &mut self.compact {
+            Some(compact) => {
+                self.buffer.push('z');
+                compact.current = compact.start;
+                compact.last = Some('z');
+            }
+            None => write!(&mut self.buffer, "Z ").unwrap(),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Layers of COLR version 0 color glyphs and their CPAL colors, read from
+//! the raw tables as ttf-parser doesn't know them.
+//!
+//! With `mono_color_glyphs` a color glyph is drawn like any other, in one
+//! path in the fill:
+//!
+//! ```
+//! use sentences2svg::{render_line, RenderOptions};
+//! use std::convert::TryInto;
+//!
+//! // DejaVu Sans with a COLR table drawing A (glyph 36) as a red A and an
+//! // o in the text color.
+//! let font = std::fs::read("fonts/DejaVuSans.ttf").unwrap();
+//! let be16 = |at: usize| u16::from_be_bytes([font[at], font[at + 1]]);
+//! let be32 = |at: usize| {
+//!     u32::from_be_bytes([font[at], font[at + 1], font[at + 2], font[at + 3]])
+//! };
+//! let colr: Vec<u8> = [0u16, 1, 0, 14, 0, 20, 2, 36, 0, 2, 36, 0, 82, 0xFFFF]
+//!     .iter()
+//!     .flat_map(|n| n.to_be_bytes())
+//!     .collect();
+//! let cpal = [0, 0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 14, 0, 0, 0, 0, 255, 255];
+//! let mut tables: Vec<([u8; 4], Vec<u8>)> = (0..be16(4) as usize)
+//!     .map(|i| {
+//!         let record = 12 + 16 * i;
+//!         let (at, len) = (be32(record + 8), be32(record + 12));
+//!         let tag = font[record..record + 4].try_into().unwrap();
+//!         (tag, font[at as usize..(at + len) as usize].to_vec())
+//!     })
+//!     .collect();
+//! tables.push((*b"COLR", colr));
+//! tables.push((*b"CPAL", cpal.to_vec()));
+//! tables.sort();
+//! let mut colored = font[..4].to_vec();
+//! colored.extend((tables.len() as u16).to_be_bytes());
+//! colored.extend([0; 6]);
+//! let mut at = 12 + 16 * tables.len();
+//! let mut data = vec![];
+//! for (tag, table) in &tables {
+//!     colored.extend(tag);
+//!     colored.extend([0; 4]);
+//!     colored.extend(((at + data.len()) as u32).to_be_bytes());
+//!     colored.extend((table.len() as u32).to_be_bytes());
+//!     data.extend(table);
+//!     data.resize(data.len().next_multiple_of(4), 0);
+//! }
+//! colored.extend(data);
+//! let faces = [ttf_parser::Face::from_slice(&colored, 0).unwrap()];
+//!
+//! let options = RenderOptions::default();
+//! let svg = render_line(&faces, "A", &options).unwrap();
+//! assert_eq!(svg.matches("<path").count(), 2);
+//! assert!(svg.contains("fill='#ff0000'"));
+//!
+//! let options = RenderOptions {
+//!     mono_color_glyphs: true,
+//!     fill: Some("black".to_string()),
+//!     ..RenderOptions::default()
+//! };
+//! let svg = render_line(&faces, "A", &options).unwrap();
+//! assert_eq!(svg.matches("<path").count(), 1);
+//! assert_eq!(svg.matches("fill=").count(), 1);
+//! assert!(svg.contains("<g fill='black'>"));
+//! ```
+
+use ttf_parser as ttf;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// How many palettes the font has, 0 without a CPAL table.
+pub fn palette_count(face: &ttf::Face) -> usize {
+    face.table_data(ttf::Tag::from_bytes(b"CPAL"))
+        .and_then(|cpal| u16_at(cpal, 4))
+        .unwrap_or_default() as usize
+}
+
+/// The palette's color for the entry as `#rrggbb`, with alpha on the end
+/// when it isn't opaque.
+fn color(face: &ttf::Face, palette: usize, entry: u16) -> Option<String> {
+    let cpal = face.table_data(ttf::Tag::from_bytes(b"CPAL"))?;
+    let entries = u16_at(cpal, 2)?;
+    let palettes = u16_at(cpal, 4)? as usize;
+    if entry >= entries || palette >= palettes {
+        return None;
+    }
+    let records = u32_at(cpal, 8)? as usize;
+    let first = u16_at(cpal, 12 + palette * 2)? as usize;
+    let at = records + (first + entry as usize) * 4;
+    let bgra = cpal.get(at..at + 4)?;
+    let (b, g, r, a) = (bgra[0], bgra[1], bgra[2], bgra[3]);
+    Some(if a == 255 {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    } else {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    })
+}
+
+/// The glyphs a color glyph is drawn with, bottom first, each with its
+/// color. `None` is the text color, as are entries the palette lacks. A
+/// glyph without layers gives `None`.
+pub fn layers(
+    face: &ttf::Face,
+    glyph: ttf::GlyphId,
+    palette: usize,
+) -> Option<Vec<(ttf::GlyphId, Option<String>)>> {
+    let colr = face.table_data(ttf::Tag::from_bytes(b"COLR"))?;
+    let base_count = u16_at(colr, 2)? as usize;
+    let base_records = u32_at(colr, 4)? as usize;
+    let layer_records = u32_at(colr, 8)? as usize;
+    // Base glyph records are sorted by glyph id.
+    let (mut low, mut high) = (0, base_count);
+    let (first, count) = loop {
+        if low >= high {
+            return None;
+        }
+        let middle = (low + high) / 2;
+        let record = base_records + middle * 6;
+        let id = u16_at(colr, record)?;
+        match id.cmp(&glyph.0) {
+            std::cmp::Ordering::Less => low = middle + 1,
+            std::cmp::Ordering::Greater => high = middle,
+            std::cmp::Ordering::Equal => {
+                break (
+                    u16_at(colr, record + 2)? as usize,
+                    u16_at(colr, record + 4)? as usize,
+                )
+            }
+        }
+    };
+    (first..first + count)
+        .map(|layer| {
+            let record = layer_records + layer * 4;
+            let id = ttf::GlyphId(u16_at(colr, record)?);
+            let entry = u16_at(colr, record + 2)?;
+            let color = match entry {
+                0xFFFF => None,
+                entry => color(face, palette, entry),
+            };
+            Some((id, color))
+        })
+        .collect()
+}
@@ -0,0 +1,222 @@
+//! The bit of TOML a `--config` file needs: comments, `[table]` headers and
+//! `key = value` lines of strings, numbers, booleans and arrays of them.
+//! Inline tables, dotted keys and dates aren't supported.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// A string, or a number as written without its underscores.
+    Text(String),
+    Array(Vec<Value>),
+}
+
+/// A `key = value` line, with the table header above it if there was one.
+#[derive(Debug)]
+pub struct Entry {
+    pub table: Option<String>,
+    pub key: String,
+    pub value: Value,
+    /// One based.
+    pub line: usize,
+}
+
+/// Reads a basic or literal string starting at the quote, returning it and
+/// what's left after the closing quote.
+fn string(text: &str) -> Result<(String, &str), String> {
+    let quote = text.chars().next().unwrap();
+    let mut out = String::new();
+    let mut chars = text[1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((out, &text[i + 2..])),
+            '\\' if quote == '"' => {
+                let escape = chars.next().map(|(_, c)| c);
+                out.push(match escape {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some(u @ ('u' | 'U')) => {
+                        let digits = if u == 'u' { 4 } else { 8 };
+                        let hex = (0..digits)
+                            .filter_map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<String>();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| {
+                                format!("\\{}{} isn't a character", u, hex)
+                            })?
+                    }
+                    _ => return Err("unknown escape in a string".to_string()),
+                });
+            }
+            c => out.push(c),
+        }
+    }
+    Err("string is never closed".to_string())
+}
+
+const UNCLOSED: &str = "array is never closed";
+
+/// Reads a value, returning it and what's left of the line after it.
+fn value(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+    match text.chars().next() {
+        Some('"') | Some('\'') => {
+            if text.starts_with("\"\"\"") || text.starts_with("'''") {
+                return Err("multi-line strings aren't supported".to_string());
+            }
+            string(text).map(|(s, rest)| (Value::Text(s), rest))
+        }
+        Some('[') => {
+            let mut items = vec![];
+            let mut rest = text[1..].trim_start();
+            loop {
+                if rest.is_empty() {
+                    return Err(UNCLOSED.to_string());
+                }
+                if let Some(after) = rest.strip_prefix(']') {
+                    return Ok((Value::Array(items), after));
+                }
+                let (item, after) = value(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after.trim_start();
+                } else if !rest.is_empty() && !rest.starts_with(']') {
+                    return Err("expected , or ] in an array".to_string());
+                }
+            }
+        }
+        Some('{') => Err("inline tables aren't supported".to_string()),
+        _ => {
+            let end = text
+                .find(|c: char| c.is_whitespace() || c == ',' || c == ']')
+                .unwrap_or(text.len());
+            let (word, rest) = text.split_at(end);
+            let value = match word {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                word if !word.is_empty()
+                    && word.chars().all(|c| {
+                        c.is_ascii_alphanumeric()
+                            || matches!(c, '+' | '-' | '.' | '_')
+                    })
+                    && word.replace('_', "").parse::<f64>().is_ok() =>
+                {
+                    Value::Text(word.replace('_', ""))
+                }
+                _ => return Err(format!("{} isn't a value", word)),
+            };
+            Ok((value, rest))
+        }
+    }
+}
+
+/// The line up to a `#` that isn't in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn expect_end(rest: &str) -> Result<(), String> {
+    match rest.trim() {
+        "" => Ok(()),
+        rest => Err(format!("unexpected {}", rest)),
+    }
+}
+
+fn key(text: &str) -> Result<(String, &str), String> {
+    let text = text.trim();
+    if text.starts_with('"') || text.starts_with('\'') {
+        return string(text);
+    }
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-')))
+        .unwrap_or(text.len());
+    if end == 0 {
+        return Err("expected a key".to_string());
+    }
+    if text[end..].trim_start().starts_with('.') {
+        return Err("dotted keys aren't supported".to_string());
+    }
+    Ok((text[..end].to_string(), &text[end..]))
+}
+
+/// The entries in the order they're written, or what's wrong and on what
+/// line.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+    let mut table = None;
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let at = |e: String| format!("line {}: {}", number + 1, e);
+        let line = strip_comment(line);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let close = header
+                .find(']')
+                .ok_or_else(|| at("table header is never closed".into()))?;
+            let (name, rest) = key(&header[..close]).map_err(at)?;
+            if !rest.trim().is_empty() {
+                return Err(at("nested tables aren't supported".to_string()));
+            }
+            expect_end(&header[close + 1..]).map_err(at)?;
+            table = Some(name);
+            continue;
+        }
+        let equals = line
+            .find('=')
+            .ok_or_else(|| at("expected key = value".to_string()))?;
+        let (name, rest) = key(&line[..equals]).map_err(at)?;
+        if !rest.trim().is_empty() {
+            return Err(at(format!("unexpected {}", rest.trim())));
+        }
+        // Arrays can run over several lines.
+        let mut source = line[equals + 1..].to_string();
+        let parsed = loop {
+            match value(&source) {
+                Err(e) if e == UNCLOSED => {
+                    let (_, next) = lines.next().ok_or_else(|| at(e))?;
+                    source.push(' ');
+                    source.push_str(strip_comment(next));
+                }
+                result => break result,
+            }
+        };
+        let (value, rest) = parsed.map_err(at)?;
+        expect_end(rest).map_err(at)?;
+        if entries
+            .iter()
+            .any(|entry: &Entry| entry.table == table && entry.key == name)
+        {
+            return Err(at(format!("{} is set twice", name)));
+        }
+        entries.push(Entry {
+            table: table.clone(),
+            key: name,
+            value,
+            line: number + 1,
+        });
+    }
+    Ok(entries)
+}
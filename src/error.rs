@@ -0,0 +1,235 @@
+use std::{io, path::PathBuf};
+
+use ttf_parser as ttf;
+
+#[derive(Debug)]
+pub enum Error {
+    FontOpen {
+        path: String,
+        source: io::Error,
+    },
+    FontBase64(base64::DecodeError),
+    FontParse(ttf::FaceParsingError),
+    FaceIndex {
+        index: u32,
+        count: u32,
+    },
+    InputRead {
+        path: String,
+        source: io::Error,
+    },
+    InputEncoding {
+        path: String,
+    },
+    InputTooLarge {
+        path: String,
+        max: u64,
+    },
+    InvalidOption {
+        option: &'static str,
+        value: String,
+    },
+    InvalidOutput(String),
+    /// What's wrong with a --config or --input-lines-from-args-file file.
+    Config {
+        path: String,
+        message: String,
+    },
+    /// A --template that isn't well formed or lacks the anchor.
+    Template {
+        path: String,
+        message: String,
+    },
+    /// Negative spacing took up the whole width.
+    NoWidth(f32),
+    MissingColumn {
+        row: usize,
+        column: usize,
+    },
+    /// The svg couldn't be drawn into a png or pdf.
+    Raster(String),
+    Create {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Exists {
+        path: PathBuf,
+    },
+    /// More than one line's file would have the same path.
+    Collision {
+        path: PathBuf,
+        /// One based.
+        lines: Vec<usize>,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Line {
+        /// One based, as in the input.
+        number: usize,
+        line: String,
+        source: Box<Error>,
+    },
+    Glyph {
+        character: char,
+        number: usize,
+        line: String,
+        source: Box<Error>,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::FontOpen { path, source } => {
+                write!(f, "Could not open {}\n{}", path, source)
+            }
+            Error::FontBase64(e) => {
+                write!(f, "font-base64 is not valid base64.\n{}", e)
+            }
+            Error::FontParse(e) => {
+                write!(f, "Error when parsing font.\n{}", e)
+            }
+            Error::FaceIndex { index, count } => write!(
+                f,
+                "There is no face {}, the font only has {} (indexes 0 to {}).",
+                index,
+                count,
+                count - 1
+            ),
+            Error::InputRead { path, source } => {
+                write!(f, "Could not read {}\n{}", path, source)
+            }
+            Error::InputEncoding { path } => {
+                write!(f, "{} is not formatted with utf8", path)
+            }
+            Error::InputTooLarge { path, max } => {
+                write!(f, "{} is bigger than the limit of {} bytes", path, max)
+            }
+            Error::InvalidOption { option, value } => {
+                write!(f, "{} is not a valid {}.", value, option)
+            }
+            Error::InvalidOutput(reason) => f.write_str(reason),
+            Error::Config { path, message } => {
+                write!(f, "{}, {}.", path, message)
+            }
+            Error::Template { path, message } => {
+                write!(f, "Could not use the template {}, {}.", path, message)
+            }
+            Error::NoWidth(width) => write!(
+                f,
+                "Spacing leaves a width of {}, it has to stay positive.",
+                width
+            ),
+            Error::MissingColumn { row, column } => {
+                write!(f, "Row {} has no column {}.", row, column)
+            }
+            Error::Raster(reason) => {
+                write!(f, "Could not draw the svg, {}.", reason)
+            }
+            Error::Create { path, source } => {
+                write!(f, "Could not create {}\n{}", path.display(), source)
+            }
+            Error::Exists { path } => write!(
+                f,
+                "{} already exists, pass --force to overwrite it or \
+                 --skip-existing to keep it.",
+                path.display()
+            ),
+            Error::Collision { path, lines } => write!(
+                f,
+                "Lines {} would all be written to {}, pass \
+                 --allow-collisions to let them share it.",
+                lines
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                path.display()
+            ),
+            Error::Write { path, source } => {
+                write!(f, "Could not write {}\n{}", path.display(), source)
+            }
+            Error::Line {
+                number,
+                line,
+                source,
+            } => {
+                write!(
+                    f,
+                    "could not write file for line {} \"{}\"\n{}",
+                    number, line, source
+                )
+            }
+            Error::Glyph {
+                character,
+                number,
+                line,
+                source,
+            } => write!(
+                f,
+                "could not write file for '{}' in line {} \"{}\"\n{}",
+                character, number, line, source
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// What the process exits with: 2 for options, fonts and input that
+    /// can't be used, 3 for files that couldn't be read or written and 1
+    /// for lines that couldn't be drawn.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::FontOpen { .. }
+            | Error::InputRead { .. }
+            | Error::Create { .. }
+            | Error::Write { .. } => 3,
+            Error::FontBase64(_)
+            | Error::FontParse(_)
+            | Error::FaceIndex { .. }
+            | Error::InputEncoding { .. }
+            | Error::InputTooLarge { .. }
+            | Error::InvalidOption { .. }
+            | Error::InvalidOutput(_)
+            | Error::Config { .. }
+            | Error::Template { .. }
+            | Error::MissingColumn { .. }
+            | Error::Exists { .. }
+            | Error::Collision { .. } => 2,
+            Error::NoWidth(_) | Error::Raster(_) => 1,
+            Error::Line { source, .. } | Error::Glyph { source, .. } => {
+                source.exit_code()
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::FontOpen { source, .. }
+            | Error::InputRead { source, .. }
+            | Error::Create { source, .. }
+            | Error::Write { source, .. } => Some(source),
+            Error::FontBase64(e) => Some(e),
+            Error::FontParse(e) => Some(e),
+            Error::Line { source, .. } | Error::Glyph { source, .. } => {
+                Some(source.as_ref())
+            }
+            Error::FaceIndex { .. }
+            | Error::InputEncoding { .. }
+            | Error::InputTooLarge { .. }
+            | Error::Exists { .. }
+            | Error::Collision { .. }
+            | Error::InvalidOption { .. }
+            | Error::InvalidOutput(_)
+            | Error::Config { .. }
+            | Error::Template { .. }
+            | Error::NoWidth(_)
+            | Error::MissingColumn { .. }
+            | Error::Raster(_) => None,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+/// Parses the file name of an output format string, which ends in the
+/// extension of the files being written.
+pub fn parse_file<'a>(
+    file: &'a str,
+    extension: &str,
+) -> nom::IResult<&'a str, Vec<Piece>> {
+    use nom::{
+        branch::alt,
+        bytes::complete::tag,
+        character::complete::{anychar, digit1},
+        combinator::{all_consuming, map, map_res, not, recognize, verify},
+        multi::many1,
+        sequence::{delimited, preceded, terminated},
+    };
+    let placeholder = alt((
+        map(tag("{}"), |_| Piece::Label(0)),
+        map(tag("{text}"), |_| Piece::Slug(64)),
+        map(tag("{file}"), |_| Piece::File),
+        map(
+            map_res(delimited(tag("{:0"), digit1, tag("}")), str::parse),
+            Piece::Label,
+        ),
+        map(
+            verify(
+                map_res(delimited(tag("{col"), digit1, tag("}")), str::parse),
+                |column: &usize| *column > 0,
+            ),
+            Piece::Column,
+        ),
+    ));
+    let ending = format!(".{}", extension);
+    let ending = ending.as_str();
+    let text = map(
+        recognize(many1(preceded(not(alt((tag("{"), tag(ending)))), anychar))),
+        |text: &str| Piece::Text(text.to_string()),
+    );
+    let (rest, pieces) = all_consuming(terminated(
+        many1(alt((placeholder, text))),
+        tag(ending),
+    ))(file)?;
+    Ok((rest, pieces))
+}
+
+#[derive(Clone)]
+pub enum Piece {
+    Text(String),
+    /// The label, zero padded to at least this many digits.
+    Label(usize),
+    /// One based column of a csv or tsv input row.
+    Column(usize),
+    /// The text of the line made safe for a file name, cut to at most this
+    /// many characters.
+    Slug(usize),
+    /// The stem of the input file the line is from.
+    File,
+}
+
+/// Lowercases the text, turns runs of whitespace into `_` and drops
+/// anything file systems could trip over.
+fn slug(text: &str, max_length: usize) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+        .chars()
+        .take(max_length)
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct FormatString {
+    pub pieces: Vec<Piece>,
+    /// Without the dot, svg unless the files are pngs.
+    pub extension: &'static str,
+}
+
+impl Default for FormatString {
+    fn default() -> Self {
+        FormatString {
+            pieces: vec![Piece::Label(0)],
+            extension: "svg",
+        }
+    }
+}
+
+impl FormatString {
+    pub fn label(
+        &self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+    ) -> String {
+        let mut name = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => name.push_str(text),
+                Piece::Label(width) => {
+                    name.push_str(&format!("{:0>1$}", label, width))
+                }
+                Piece::Column(column) => name.push_str(
+                    columns.get(column - 1).map_or("", String::as_str),
+                ),
+                Piece::Slug(max_length) => {
+                    name.push_str(&slug(text, *max_length))
+                }
+                Piece::File => name.push_str(file),
+            }
+        }
+        name + "." + self.extension
+    }
+
+    /// Whether two lines can end up with the same name from having the
+    /// same text or file, with no number to tell them apart.
+    pub fn can_collide(&self) -> bool {
+        let has = |f: fn(&Piece) -> bool| self.pieces.iter().any(f);
+        has(|piece| matches!(piece, Piece::Slug(_) | Piece::File))
+            && !has(|piece| matches!(piece, Piece::Label(_)))
+    }
+}
@@ -0,0 +1,48 @@
+//! Turns lines of text into svg paths using the outlines of a font.
+
+pub mod args_file;
+pub mod bidi;
+pub mod builder;
+pub mod color;
+pub mod compose;
+pub mod config;
+pub mod error;
+pub mod format;
+mod outline;
+pub mod output;
+pub mod pdf;
+pub mod raster;
+pub mod render;
+pub mod template;
+
+pub use error::Error;
+pub use render::{
+    measure_line, positioned_glyphs, render, render_line, PositionedGlyph,
+    RenderOptions,
+};
+
+/// What a message on stderr starts with, in color only when stderr is a
+/// terminal so redirected logs don't get escape codes.
+pub struct Label {
+    color: &'static str,
+    text: &'static str,
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if std::io::IsTerminal::is_terminal(&std::io::stderr()) {
+            write!(f, "\u{001b}[{}m{}: \u{001b}[0m", self.color, self.text)
+        } else {
+            write!(f, "{}: ", self.text)
+        }
+    }
+}
+
+pub const YELLOW_WARNING: Label = Label {
+    color: "33;1",
+    text: "warning",
+};
+pub const RED_ERROR: Label = Label {
+    color: "31;1",
+    text: "error",
+};
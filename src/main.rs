@@ -1,132 +1,605 @@
-#![feature(path_try_exists)]
 use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     fmt::Write,
     fs::File,
-    io::{self, Read},
-    path::PathBuf,
-    str::FromStr,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, ErrorKind};
+use xmlwriter::Indent;
+
+use sentences2svg::{
+    args_file,
+    bidi::Direction,
+    color,
+    compose::compose,
+    config::{self, Entry, Value},
+    error::Error,
+    format::{parse_file, FormatString, Piece},
+    output::{Archive, Compression, Directory, Output, Overwrite},
+    pdf, raster,
+    render::{
+        advance_width, average_advance, canvas_size, family_name, glyphs,
+        is_blank, line_metrics, lookup, render, render_document, render_titled,
+        Align, CacheStats, CanvasFit, Case, Glyph, GlyphOrigin, KerningSource,
+        LineHeight, LineMetrics, Metadata, RenderOptions, RootTag,
+    },
+    template::Template,
+    RED_ERROR, YELLOW_WARNING,
+};
 use ttf_parser as ttf;
-use xmlwriter::*;
 
-struct Builder {
-    pub buffer: String,
-    pub offset: f32,
-}
+/// Pangram rendered by `--benchmark`.
+const TEST_STRING: &str = "The quick brown fox jumps over the lazy dog.";
 
-impl ttf_parser::OutlineBuilder for Builder {
-    fn move_to(&mut self, x: f32, y: f32) {
-        write!(&mut self.buffer, "M {} {} ", x + self.offset, -y).unwrap();
+/// Splits comma or tab separated text into rows of fields. Commas honour
+/// double quoted fields, with `""` standing for a literal quote.
+fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if delimiter == ',' && quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if delimiter == ',' && field.is_empty() => quoted = true,
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            c if c == delimiter && !quoted => {
+                row.push(std::mem::take(&mut field))
+            }
+            c => field.push(c),
+        }
     }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
 
-    fn line_to(&mut self, x: f32, y: f32) {
-        write!(&mut self.buffer, "L {} {} ", x + self.offset, -y).unwrap();
+/// Whether the name matches a glob of `*`, `?` and `[...]` classes, which
+/// `[!...]` negates.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len())
+            .any(|skip| glob_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let close = match pattern.iter().skip(2).position(|&c| c == ']') {
+                Some(close) => close + 2,
+                None => {
+                    return name.first() == Some(&'[')
+                        && glob_match(&pattern[1..], &name[1..])
+                }
+            };
+            let c = match name.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+            let (negated, class) = match pattern[1] {
+                '!' => (true, &pattern[2..close]),
+                _ => (false, &pattern[1..close]),
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= (class[i]..=class[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found != negated && glob_match(&pattern[close + 1..], &name[1..])
+        }
+        Some(&c) => {
+            name.first() == Some(&c) && glob_match(&pattern[1..], &name[1..])
+        }
     }
+}
 
-    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
-        write!(
-            &mut self.buffer,
-            "Q {} {} {} {} ",
-            x1 + self.offset,
-            -y1,
-            x + self.offset,
-            -y
-        )
-        .unwrap();
+/// The files a `-i` pattern names, sorted. Done here rather than by the
+/// shell so quoted patterns work the same everywhere. Hidden files only
+/// match a part starting with a dot.
+fn expand_input(pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let read_error = |source| Error::InputRead {
+        path: pattern.to_string(),
+        source,
+    };
+    if !pattern.contains(['*', '?', '[']) && Path::new(pattern).is_dir() {
+        // Every file right inside it, like a `*` would match.
+        let mut paths = std::fs::read_dir(pattern)
+            .map_err(read_error)?
+            .flatten()
+            .filter(|entry| {
+                !entry.file_name().to_string_lossy().starts_with('.')
+            })
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(read_error(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the directory has no files",
+            )));
+        }
+        paths.sort();
+        return Ok(paths);
+    }
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![PathBuf::from(pattern)]);
+    }
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !part.contains(['*', '?', '[']) {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        }
+        let part = part.chars().collect::<Vec<_>>();
+        let mut matched = vec![];
+        for path in &paths {
+            let directory = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path
+            };
+            let entries = match std::fs::read_dir(directory) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = match name.to_str() {
+                    Some(name) => name.chars().collect::<Vec<_>>(),
+                    None => continue,
+                };
+                if (name[0] != '.' || part[0] == '.')
+                    && glob_match(&part, &name)
+                {
+                    matched.push(path.join(entry.file_name()));
+                }
+            }
+        }
+        paths = matched;
+    }
+    if paths.is_empty() {
+        return Err(read_error(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no files match the pattern",
+        )));
     }
+    paths.sort();
+    Ok(paths)
+}
 
-    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
-        write!(
-            &mut self.buffer,
-            "C {} {} {} {} {} {} ",
-            x1 + self.offset,
-            -y1,
-            x2 + self.offset,
-            -y2,
-            x + self.offset,
-            -y
-        )
-        .unwrap();
+/// Colors are passed on as is, so they only have to look like one: a hex
+/// color, a name like `none` or `red`, or a function like `rgb(...)`.
+fn parse_color(
+    option: &'static str,
+    color: Option<&str>,
+) -> Result<Option<String>, Error> {
+    let color = match color {
+        Some(color) => color,
+        None => return Ok(None),
+    };
+    let valid = match color.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8)
+                && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => match color.find('(') {
+            Some(open) => {
+                color.ends_with(')')
+                    && color[..open].chars().all(|c| c.is_ascii_alphabetic())
+                    && !color.contains(['\'', '"', '<', '&'])
+            }
+            None => {
+                !color.is_empty()
+                    && color.chars().all(|c| c.is_ascii_alphabetic())
+            }
+        },
+    };
+    if valid {
+        Ok(Some(color.to_string()))
+    } else {
+        Err(invalid_option(option, color))
     }
+}
 
-    fn close(&mut self) {
-        write!(&mut self.buffer, "Z ").unwrap();
+fn invalid_option(option: &'static str, value: &str) -> Error {
+    Error::InvalidOption {
+        option,
+        value: value.to_string(),
     }
 }
 
-const RED_ERROR: &str = "\u{001b}[31;1merror: \u{001b}[0m";
+struct Args {
+    /// The font given with -f followed by any fallback fonts.
+    pub faces: Vec<ttf::Face<'static>>,
+    pub lines: Vec<InputLine>,
+    /// The stem of each input file, for `{file}` in output names.
+    pub files: Vec<String>,
+    /// The name of each input file, for --title-from-filename, or nothing.
+    pub titles: Vec<String>,
+    /// Files are numbered from 0 in each input, since `{file}` tells them
+    /// apart.
+    pub per_file: bool,
+    /// Inputs that couldn't be read with --continue-on-error.
+    pub input_errors: Vec<Error>,
+    pub output: Output,
+    pub options: RenderOptions,
+    pub continue_on_error: bool,
+    /// Where to write the character to glyph id table, if anywhere.
+    pub glyph_index_map: Option<PathBuf>,
+    pub advance_width_table: Option<PathBuf>,
+    /// Where to write each line's layout as json.
+    pub metrics: Option<PathBuf>,
+    /// Time rendering the lines instead of writing them.
+    pub benchmark: bool,
+    /// Only list the characters each line is missing from the font.
+    pub measure_missing: bool,
+    /// Only describe the font and how much of the input it covers.
+    pub inspect: bool,
+    /// Svg the rendered paths are spliced into.
+    pub template: Option<Template>,
+    pub preview: bool,
+    pub bbox_union_report: bool,
+    /// Exit with an error once done if any character had no glyph.
+    pub strict: bool,
+    pub glyph_padding_cells: bool,
+    pub single_file: bool,
+    /// Number the first file gets.
+    pub start_index: usize,
+    pub glyph_render_stats: bool,
+    pub glyph_cache_stats: bool,
+    /// Threads lines are rendered on.
+    pub jobs: usize,
+    pub progress: bool,
+    pub format: Format,
+    pub dry_run: bool,
+    /// Lets lines share a file name, the later one replacing the earlier
+    /// or being skipped.
+    pub allow_collisions: bool,
+}
 
-fn parse_file(file: &str) -> nom::IResult<&str, (&str, &str)> {
-    use nom::{
-        bytes::complete::{tag, take_till},
-        sequence::terminated,
-    };
-    let (rest, left) = take_till(|c| c == '{')(file)?;
-    let (rest, _) = tag("{}")(rest)?;
-    let (rest, right) = terminated(take_till(|c| c == '.'), tag(".svg"))(rest)?;
-    Ok((rest, (left, right)))
+/// What each svg is written as.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Svg,
+    /// Drawn this many pixels per unit.
+    Png(f64),
+    Pdf,
 }
 
-fn format_error<E: std::fmt::Display>(
-    message: String,
-) -> impl FnOnce(E) -> anyhow::Error {
-    move |e: E| anyhow::anyhow!("{}{}\n{}", RED_ERROR, message, e)
+/// Totals for `--glyph-render-stats`, of what was written.
+#[derive(Default)]
+struct Stats<'a> {
+    lines: usize,
+    glyphs: usize,
+    unique: Vec<Glyph<'a>>,
+    bytes: usize,
 }
 
-fn format_message_no_error(message: String) -> anyhow::Error {
-    anyhow::anyhow!("{}{}", RED_ERROR, message)
+impl<'a> Stats<'a> {
+    fn add(&mut self, glyphs: &[Glyph<'a>], bytes: usize) {
+        self.glyphs += glyphs.len();
+        self.bytes += bytes;
+        for &glyph in glyphs {
+            if !self.unique.iter().any(|seen| {
+                std::ptr::eq(seen.face, glyph.face) && seen.id == glyph.id
+            }) {
+                self.unique.push(glyph);
+            }
+        }
+    }
 }
 
-fn format_error_no_message<E: std::fmt::Display>(err: E) -> anyhow::Error {
-    anyhow::anyhow!("{}{}", RED_ERROR, err)
+/// A line for the `--metrics` file.
+struct MetricsEntry {
+    /// Unset when the svg couldn't be written.
+    file: Option<PathBuf>,
+    text: String,
+    metrics: LineMetrics,
 }
 
-#[derive(Default)]
-struct FormatString {
-    left: String,
-    right: String,
+/// Quotes and escapes the text as a json string.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            '\r' => json.push_str("\\r"),
+            c if c < ' ' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
 }
 
-impl FormatString {
-    pub fn label(&self, label: &str) -> String {
-        format!("{}{}{}.svg", self.left, label, self.right)
+/// One object per line, failed ones marked as such, written by hand as
+/// it's the only json there is.
+fn metrics_json(entries: &[MetricsEntry]) -> String {
+    let mut json = String::from("[\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let metrics = &entry.metrics;
+        json.push_str("  {");
+        match &entry.file {
+            Some(file) => write!(
+                json,
+                "\"file\": {}, ",
+                json_string(&file.to_string_lossy())
+            ),
+            None => write!(json, "\"file\": null, \"failed\": true, "),
+        }
+        .unwrap();
+        write!(
+            json,
+            "\"text\": {}, \"width\": {}, \"height\": {}, \"ascent\": {}, \
+             \"descent\": {}, ",
+            json_string(&entry.text),
+            metrics.width,
+            metrics.height,
+            metrics.ascent,
+            metrics.descent
+        )
+        .unwrap();
+        if metrics.scale != 1.0 {
+            write!(
+                json,
+                "\"width_px\": {}, \"height_px\": {}, ",
+                metrics.width * metrics.scale,
+                metrics.height * metrics.scale
+            )
+            .unwrap();
+        }
+        json.push_str("\"glyphs\": [");
+        for (j, glyph) in metrics.glyphs.iter().enumerate() {
+            if j > 0 {
+                json.push_str(", ");
+            }
+            write!(
+                json,
+                "{{\"char\": {}, \"glyph_id\": {}, \"x_offset\": {}, \
+                 \"advance\": {}}}",
+                json_string(&glyph.c.to_string()),
+                glyph.id.0,
+                glyph.x_offset,
+                glyph.advance
+            )
+            .unwrap();
+        }
+        json.push_str("]}");
+        if i + 1 < entries.len() {
+            json.push(',');
+        }
+        json.push('\n');
     }
+    json.push_str("]\n");
+    json
 }
 
-struct Output {
-    format: FormatString,
-    directory: PathBuf,
+/// The `--progress` status line, redrawn a few times a second at most.
+struct Progress {
+    total: usize,
+    start: Instant,
+    drawn: Instant,
 }
 
-impl Output {
-    pub fn write_file(
-        &self,
-        label: &str,
-        svg: XmlWriter,
-    ) -> anyhow::Result<()> {
-        use std::io::Write;
-        let mut path = self.directory.clone();
-        path.push(self.format.label(label));
-        let mut file = File::create(&path).map_err(format_error(format!(
-            "Could not create {}",
-            path.as_os_str().to_str().unwrap()
-        )))?;
-        let text = svg.end_document().into_bytes();
-        file.write_all(&text).map_err(format_error_no_message)?;
+impl Progress {
+    fn new(total: usize) -> Self {
+        let now = Instant::now();
+        Progress {
+            total,
+            start: now,
+            drawn: now,
+        }
+    }
 
-        Ok(())
+    fn update(&mut self, done: usize) {
+        if self.drawn.elapsed() < Duration::from_millis(250) {
+            return;
+        }
+        self.drawn = Instant::now();
+        let seconds = self.start.elapsed().as_secs_f64();
+        let rate = done as f64 / seconds;
+        let eta = ((self.total - done) as f64 / rate.max(f64::EPSILON)) as u64;
+        let eta = match eta {
+            eta if eta >= 3600 => {
+                format!("{}h{}m", eta / 3600, eta % 3600 / 60)
+            }
+            eta if eta >= 60 => format!("{}m{}s", eta / 60, eta % 60),
+            eta => format!("{}s", eta),
+        };
+        // \r\x1b[K goes back over the last status line.
+        eprint!(
+            "\r\u{001b}[K{}/{} lines, {:.0} files/s, ETA {}",
+            done, self.total, rate, eta
+        );
+    }
+
+    /// Clears the status line for whatever gets printed next.
+    fn finish(&self) {
+        eprint!("\r\u{001b}[K");
     }
 }
 
-struct Args {
-    pub face: ttf::Face<'static>,
+struct InputLine {
     pub text: String,
-    pub output: Output,
+    /// Every field of the row when the input is csv or tsv.
+    pub columns: Vec<String>,
+    /// How many identical lines in a row this one stands for.
+    pub repeats: usize,
+    /// Position in the input, kept when --lines leaves others out.
+    pub index: usize,
+    /// Which input file the line is from.
+    pub file: usize,
+    /// Position in that file.
+    pub file_index: usize,
+    /// What an --input-lines-from-args-file gives the line instead of
+    /// --fill and --size.
+    pub fill: Option<String>,
+    pub size: Option<f32>,
+}
+
+/// Parses the command line with the options of the --config file put in
+/// front, left out where the command line gives them itself.
+fn get_matches(
+    app: App<'static, 'static>,
+) -> Result<ArgMatches<'static>, Error> {
+    let given = app
+        .clone()
+        .get_matches_safe()
+        .unwrap_or_else(|e| exit_usage(e));
+    let path = match given.value_of("config") {
+        Some(path) => path,
+        None => return Ok(given),
+    };
+    let text =
+        std::fs::read_to_string(path).map_err(|source| Error::InputRead {
+            path: path.to_string(),
+            source,
+        })?;
+    let failure = |message: String| Error::Config {
+        path: path.to_string(),
+        message,
+    };
+    let text_of = |value: &Value| match value {
+        Value::Text(text) => Some(text.clone()),
+        Value::Bool(_) | Value::Array(_) => None,
+    };
+    let unknown = |line: usize, key: &str| {
+        failure(format!("line {}: {} isn't an option", line, key))
+    };
+    let mut arguments: Vec<String> = vec![];
+    // Which key each argument came from, to name it in errors.
+    let mut keys = vec![];
+    for Entry {
+        table,
+        key,
+        value,
+        line,
+    } in config::parse(&text).map_err(failure)?
+    {
+        let bad_value =
+            || failure(format!("line {}: {} can't be set to that", line, key));
+        let (option, values) = match table.as_deref() {
+            // Axes add to --variation, later ones winning.
+            Some("variations") => {
+                let value = text_of(&value).ok_or_else(bad_value)?;
+                ("variation".to_string(), vec![format!("{}={}", key, value)])
+            }
+            Some(table) => {
+                return Err(failure(format!(
+                    "line {}: there is no [{}] table",
+                    line, table
+                )))
+            }
+            None => {
+                let option = key.replace('_', "-");
+                if option == "config" {
+                    return Err(failure(format!(
+                        "line {}: a config can't load another",
+                        line
+                    )));
+                }
+                // --input is the only option named differently inside.
+                let name = if option == "input" { "text" } else { &option };
+                let values = match &value {
+                    Value::Bool(true) => vec![],
+                    // A flag can't be turned off, so a false one only has
+                    // to exist.
+                    Value::Bool(false) => {
+                        let probe = [
+                            env!("CARGO_PKG_NAME").to_string(),
+                            format!("--{}", option),
+                        ];
+                        if let Err(e) = app.clone().get_matches_from_safe(probe)
+                        {
+                            if e.kind == ErrorKind::UnknownArgument {
+                                return Err(unknown(line, &key));
+                            }
+                        }
+                        continue;
+                    }
+                    Value::Text(text) => vec![text.clone()],
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|item| text_of(item).ok_or_else(bad_value))
+                        .collect::<Result<_, _>>()?,
+                };
+                if given.occurrences_of(name) > 0 {
+                    continue;
+                }
+                (option, values)
+            }
+        };
+        if values.is_empty() {
+            arguments.push(format!("--{}", option));
+            keys.push((line, key.clone()));
+        }
+        for value in values {
+            arguments.push(format!("--{}={}", option, value));
+            keys.push((line, key.clone()));
+        }
+    }
+    let mut command_line = std::env::args_os().collect::<Vec<_>>();
+    command_line.splice(1..1, arguments.iter().map(Into::into));
+    match app.get_matches_from_safe(command_line) {
+        Ok(matches) => Ok(matches),
+        Err(e) if e.kind == ErrorKind::UnknownArgument => {
+            let argument = e.info.as_ref().and_then(|info| info.first());
+            let key = argument.and_then(|argument| {
+                let at = arguments.iter().position(|config| {
+                    config.split('=').next() == argument.split('=').next()
+                })?;
+                Some(&keys[at])
+            });
+            match key {
+                Some((line, key)) => Err(unknown(*line, key)),
+                None => exit_usage(e),
+            }
+        }
+        Err(e) => exit_usage(e),
+    }
+}
+
+/// Like `clap::Error::exit`, but with the code `Error::exit_code` gives
+/// other unusable options.
+fn exit_usage(e: clap::Error) -> ! {
+    if !e.use_stderr() {
+        // --help and --version.
+        e.exit()
+    }
+    eprintln!("{}", e.message);
+    std::process::exit(2)
 }
 
-fn parse_arguments() -> anyhow::Result<Args> {
+fn parse_arguments() -> Result<Args, Error> {
     let app = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
@@ -137,7 +610,13 @@ fn parse_arguments() -> anyhow::Result<Args> {
                 .long("font")
                 .value_name("FILE")
                 .help("Path to font for conversion.")
-                .required(true),
+                .conflicts_with("font-base64"),
+        )
+        .arg(
+            Arg::with_name("font-base64")
+                .long("font-base64")
+                .value_name("DATA")
+                .help("Font for conversion given inline as base64 data."),
         )
         .arg(
             Arg::with_name("text")
@@ -146,9 +625,15 @@ fn parse_arguments() -> anyhow::Result<Args> {
                 .value_name("FILE")
                 .required_unless("text")
                 .default_value("./lines.txt")
+                .multiple(true)
+                .number_of_values(1)
+                // Or clap would take the -- for stdin as the end of options.
+                .allow_hyphen_values(true)
                 .help(
                     "Path to the text file that'll be turned into an SVG. If \
-                     specified with -- then it'll use stdin.",
+                     specified with -- then it'll use stdin. Can be given \
+                     more than once, as a pattern like 'chapters/*.txt' or \
+                     as a directory, and the files are read in turn.",
                 ),
         )
         .arg(
@@ -167,167 +652,3119 @@ However you can change how it looks by specifying the output directory
 then using {} to specify where the numbers fit in.
 For example, if i wanted to have the files look like line_1.svg, line_2.svg
 inside of the 'output' folder, then it'll look like this.
-ourput/line_{}.svg",
+ourput/line_{}.svg
+Use - to print the svgs to stdout instead, see --separator.
+Use base64:- to print each svg base64 encoded on its own line of stdout,
+handy for embedding them in JSON.
+Ending in .zip writes one archive instead, as in out.zip or out.zip/line_{}.svg
+to name the files inside it.",
                 ),
-        );
-    let matches = app.get_matches();
-    let font: ttf::Face<'static> = {
-        let font = matches.value_of("font").unwrap();
-        let mut file = File::open(font)
-            .map_err(format_error(format!("Could not open {}", font)))?;
-        let mut buffer = Box::new(vec![]);
-        file.read_to_end(&mut buffer)
-            .map_err(format_error_no_message)?;
-        // We're leaking here as one and only one font will
-        // ever be used within the lifetime of this program.
-        // so leaking here is an act of convenience to keep
-        // all initilization code in parse_arguments.
-        let buffer = buffer.leak();
-        ttf::Face::from_slice(buffer, 0)
-            .map_err(format_error("Error when parsing font.".to_string()))?
-    };
-
-    let text: String = {
-        let input = matches.value_of("text").unwrap();
-        if input == "--" {
-            let mut stdin = io::stdin();
-            let mut buffer = vec![];
-            stdin
-                .read_to_end(&mut buffer)
-                .map_err(format_error_no_message)?;
-            String::from_utf8(buffer).map_err(format_error(
-                "stdin is not formatted with utf8".to_string(),
-            ))?
-        } else {
-            let mut file = File::open(input)
-                .map_err(format_error(format!("Could not open {}", input)))?;
-            let mut buffer = vec![];
-            file.read_to_end(&mut buffer)
-                .map_err(format_error_no_message)?;
-            String::from_utf8(buffer).map_err(format_error(format!(
-                "{} is not formatted with utf8",
-                input
-            )))?
-        }
-    };
-
-    let output: Output = {
-        let output = matches.value_of("output").unwrap();
-        let mut path =
-            PathBuf::from_str(output).map_err(format_error_no_message)?;
-
-        match path.extension().map(|ext| ext.to_str()) {
-            Some(Some("svg")) => {
-                let format = {
-                    let file = path
-                        .file_name()
-                        .ok_or_else(|| {
-                            format_message_no_error(
-                                "Path has no name.".to_string(),
-                            )
-                        })?
-                        .to_str()
-                        .unwrap();
-                    let (_, (left, right)) =
-                        parse_file(file).map_err(|_| {
-                            format_error_no_message(
-                                "output not formatted correctly.",
-                            )
-                        })?;
-                    FormatString {
-                        left: left.to_string(),
-                        right: right.to_string(),
-                    }
-                };
-
-                path.pop();
-                std::fs::create_dir_all(&path).unwrap();
-                Output {
-                    format,
-                    directory: path,
-                }
-            }
-            Some(Some(ext)) => {
-                return Err(format_message_no_error(format!(
-                    "{} is not a valid output type.",
-                    ext
-                )))
-            }
-            Some(None) => {
-                return Err(format_message_no_error(
-                    "extention is not utf8 formatted.".to_string(),
-                ))
-            }
-            None => {
-                std::fs::create_dir_all(&path).unwrap();
-                Output {
-                    format: FormatString::default(),
-                    directory: path,
-                }
-            }
-        }
-    };
-    Ok(Args {
-        face: font,
-        text,
-        output,
-    })
-}
-
-fn main() {
-    let Args { face, text, output } = match parse_arguments() {
-        Ok(args) => args,
-        Err(e) => {
-            println!("{}", e);
-            std::process::exit(1);
-        }
-    };
-
-    for line in text.lines() {
-        let chars = line
-            .chars()
-            .filter_map(|c| face.glyph_index(c))
-            .collect::<Vec<_>>();
-        let mut w = XmlWriter::new(Options {
-            use_single_quote: true,
-            ..Default::default()
-        });
-        w.start_element("svg");
-        w.write_attribute("xmlns", "http://www.w3.org/2000/svg");
-        
-        let height = chars
-            .iter()
-            .filter_map(|id| face.glyph_bounding_box(*id))
-            .map(|bounding_box| bounding_box.height())
-            .max()
-            .unwrap_or_default();
-        let width: u16 = chars
-            .iter()
-            .filter_map(|id| face.glyph_hor_advance(*id))
-            .sum();
-        w.write_attribute("width", &width);
-        w.write_attribute("height", &height);
-        let _ = chars.iter().copied().fold(0, |offset, glyph_id| {
-            let mut builder = Builder {
-                buffer: String::new(),
-                offset: offset as f32,
-            };
-            let advance = face.glyph_hor_advance(glyph_id).unwrap_or_default();
-            if face.outline_glyph(glyph_id, &mut builder).is_some() {
-                let path: &str = &builder.buffer;
-                w.start_element("path");
-                w.write_attribute("d", path);
-                w.end_element();
-            }
-            offset + advance
-        });
-        w.end_element();
-        if let Err(e) = output
-            .write_file(line, w)
-            .map_err(format_error(format!("could not write file for line \"{}\"", line)))
-        {
-            println!("{}", e);
-            std::process::exit(1);
-        }
+        )
+        .arg(
+            Arg::with_name("baseline-offset")
+                .long("baseline-offset")
+                .value_name("PIXELS")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .help(
+                    "Moves the baseline down by the given amount. Negative \
+                     values move it up.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-per-file")
+                .long("glyph-per-file")
+                .help(
+                    "Writes every character to its own SVG, numbered across \
+                     all lines.",
+                ),
+        )
+        .arg(
+            Arg::with_name("continue-on-error")
+                .long("continue-on-error")
+                .alias("keep-going")
+                .help(
+                    "Skips lines that fail to render instead of stopping. \
+                     Still exits with an error at the end.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-advance-scale")
+                .long("glyph-advance-scale")
+                .value_name("FACTOR")
+                .default_value("1")
+                .help(
+                    "Multiplies the spacing between glyphs without \
+                     stretching the glyphs themselves.",
+                ),
+        )
+        .arg(
+            Arg::with_name("shard-size")
+                .long("shard-size")
+                .value_name("COUNT")
+                .help(
+                    "Splits the output into numbered subfolders (000, 001, \
+                     ...) holding at most COUNT files each.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-bbox-fallback")
+                .long("glyph-bbox-fallback")
+                .help(
+                    "Derives the advance from the glyph's bounding box when \
+                     the font reports a zero or implausibly large one.",
+                ),
+        )
+        .arg(
+            Arg::with_name("no-newline-split")
+                .long("no-newline-split")
+                .help("Renders the whole input as a single SVG."),
+        )
+        .arg(
+            Arg::with_name("embolden")
+                .long("embolden")
+                .value_name("UNITS")
+                .default_value("0")
+                .help(
+                    "Fakes a bold weight by growing each outline by the \
+                     given amount.",
+                ),
+        )
+        .arg(
+            Arg::with_name("output-metadata")
+                .long("output-metadata")
+                .help(
+                    "Records the generator version, font family and \
+                     creation time in a <metadata> element.",
+                ),
+        )
+        .arg(
+            Arg::with_name("no-metadata")
+                .long("no-metadata")
+                .help(
+                    "Leaves out the <metadata> element, for reproducible \
+                     output.",
+                ),
+        )
+        .arg(
+            Arg::with_name("input-format")
+                .long("input-format")
+                .value_name("FORMAT")
+                .possible_values(&["plain", "csv", "tsv"])
+                .default_value("plain")
+                .help(
+                    "Reads the input as plain lines or as csv/tsv rows. \
+                     Columns can be used in the output name with {colN}.",
+                ),
+        )
+        .arg(
+            Arg::with_name("text-column")
+                .long("text-column")
+                .value_name("N")
+                .default_value("1")
+                .help("Which column of a csv/tsv row gets rendered."),
+        )
+        .arg(
+            Arg::with_name("max-height")
+                .long("max-height")
+                .value_name("HEIGHT")
+                .help(
+                    "Scales down any line taller than HEIGHT, keeping its \
+                     aspect ratio.",
+                ),
+        )
+        .arg(
+            Arg::with_name("round-trip-test")
+                .long("round-trip-test")
+                .help(
+                    "Parses every written path back and warns when its \
+                     bounds disagree with the font's glyph bounding box.",
+                ),
+        )
+        .arg(
+            Arg::with_name("line")
+                .long("line")
+                .value_name("TEXT")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Renders TEXT instead of reading an input file. Can be \
+                     given more than once, each one becomes its own SVG.",
+                ),
+        )
+        .arg(
+            Arg::with_name("input-lines-from-args-file")
+                .long("input-lines-from-args-file")
+                .value_name("FILE")
+                .conflicts_with_all(&[
+                    "line",
+                    "benchmark",
+                    "single-file",
+                    "glyph-per-file",
+                    "title-from-filename",
+                ])
+                .help(
+                    "Reads the lines from a JSON array of objects like \
+                     {\"text\": \"Hi\", \"fill\": \"red\", \"size\": 32} \
+                     instead of an input file. fill and size can be left \
+                     out, and override --fill and --size for that line.",
+                ),
+        )
+        .arg(
+            Arg::with_name("transform")
+                .long("transform")
+                .value_name("TRANSFORM")
+                .allow_hyphen_values(true)
+                .help(
+                    "Wraps the glyphs in a <g> with this SVG transform, \
+                     passed through as is. The canvas is not resized to \
+                     match.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-index-map")
+                .long("glyph-index-map")
+                .value_name("FILE")
+                .help(
+                    "Writes a tab separated table of every input character \
+                     and the glyph id it resolved to.",
+                ),
+        )
+        .arg(
+            Arg::with_name("optimize-paths")
+                .long("optimize-paths")
+                .help(
+                    "Merges collinear lines and drops commands that draw \
+                     nothing to shrink the output.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-spacing-from-metrics")
+                .long("glyph-spacing-from-metrics")
+                .help(
+                    "Uses the font's GDEF glyph classes so mark glyphs \
+                     don't add to the advance.",
+                ),
+        )
+        .arg(
+            Arg::with_name("kerning-source")
+                .long("kerning-source")
+                .value_name("TABLE")
+                .possible_values(&["kern", "gpos", "both"])
+                .help(
+                    "Where kerning comes from: the old kern table, GPOS \
+                     pair adjustments or both added up. GPOS when the font \
+                     kerns there and kern otherwise when left out, so fonts \
+                     with both aren't kerned twice.",
+                ),
+        )
+        .arg(
+            Arg::with_name("svg-version")
+                .long("svg-version")
+                .value_name("VERSION")
+                .help(
+                    "Writes a version attribute on the root element, for \
+                     consumers that insist on e.g. version='1.1'.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-winding-fix-auto")
+                .long("glyph-winding-fix-auto")
+                .help(
+                    "Detects glyphs whose counters wind the wrong way and \
+                     reverses just those contours.",
+                ),
+        )
+        .arg(
+            Arg::with_name("input-trim")
+                .long("input-trim")
+                .help(
+                    "Strips leading and trailing whitespace from each line \
+                     before rendering.",
+                ),
+        )
+        .arg(
+            Arg::with_name("benchmark")
+                .long("benchmark")
+                .value_name("ITERATIONS")
+                .help(
+                    "Renders a pangram the given number of times and reports \
+                     the throughput without writing any files.",
+                ),
+        )
+        .arg(
+            Arg::with_name("collapse-whitespace")
+                .long("collapse-whitespace")
+                .help(
+                    "Replaces every run of whitespace in a line with a single \
+                     space, like HTML does.",
+                ),
+        )
+        .arg(
+            Arg::with_name("max-advance-ratio")
+                .long("max-advance-ratio")
+                .value_name("RATIO")
+                .help(
+                    "Caps every glyph advance at this multiple of the font's \
+                     average advance, warning when one is clamped.",
+                ),
+        )
+        .arg(
+            Arg::with_name("emit-baseline-path")
+                .long("emit-baseline-path")
+                .help(
+                    "Records the baseline y coordinate in a data-baseline \
+                     attribute on the root element, and with stacked lines \
+                     each line's on a <g> around it.",
+                ),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .value_name("FILE")
+                .help(
+                    "Svg to render into, a copy per line. The glyph paths \
+                     take the place of the --template-anchor element, moved \
+                     to its x and y, or fill it if it's a <g>.",
+                ),
+        )
+        .arg(
+            Arg::with_name("template-anchor")
+                .long("template-anchor")
+                .value_name("ID")
+                .requires("template")
+                .help(
+                    "Id of the --template element the text goes in, \
+                     text-slot by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fit-anchor")
+                .long("fit-anchor")
+                .requires("template")
+                .help(
+                    "Scales the text down or up to the width and height of \
+                     the --template-anchor element.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-order")
+                .long("glyph-order")
+                .value_name("ORDER")
+                .possible_values(&["ttf", "visual"])
+                .default_value("ttf")
+                .help(
+                    "Order glyphs are laid out in. visual moves Indic \
+                     pre-base vowel signs in front of their consonant \
+                     cluster, ttf leaves them after it.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fit-to-square")
+                .long("fit-to-square")
+                .help(
+                    "Pads the shorter side so every svg is square, with the \
+                     text centered.",
+                ),
+        )
+        .arg(
+            Arg::with_name("contour-count-limit")
+                .long("contour-count-limit")
+                .value_name("COUNT")
+                .help(
+                    "Skips, with a warning, any glyph made of more contours \
+                     than this, which usually means the font is corrupt.",
+                ),
+        )
+        .arg(
+            Arg::with_name("preview")
+                .long("preview")
+                .help(
+                    "Opens the first svg written in the default viewer. Does \
+                     nothing on CI or without a display.",
+                ),
+        )
+        .arg(
+            Arg::with_name("input-escapes")
+                .long("input-escapes")
+                .help(
+                    "Turns code point escapes like \\u{1F600} or U+0041 in the \
+                     input into the characters they name.",
+                ),
+        )
+        .arg(
+            Arg::with_name("dedupe-paths")
+                .long("dedupe-paths")
+                .help(
+                    "Draws each distinct glyph outline once and repeats it \
+                     with <use> elements.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-bbox-union-report")
+                .long("glyph-bbox-union-report")
+                .help(
+                    "Prints the bounding box of every svg written stacked \
+                     one above the next, as x y width height, to stderr once \
+                     rendering is done: the widest one's width and their \
+                     heights added up.",
+                ),
+        )
+        .arg(
+            Arg::with_name("svg-root-tag")
+                .long("svg-root-tag")
+                .value_name("TAG")
+                .possible_values(&["svg", "symbol", "g"])
+                .default_value("svg")
+                .help(
+                    "Top-level element of each file. symbol gets an id and a \
+                     viewBox for sprites, g can be inlined into another svg.",
+                ),
+        )
+        .arg(
+            Arg::with_name("antialias-hint")
+                .long("antialias-hint")
+                .value_name("HINT")
+                .possible_values(&["auto", "none"])
+                .default_value("auto")
+                .help(
+                    "none rounds every coordinate to a whole pixel and asks \
+                     for crispEdges rendering, for pixel-perfect small text.",
+                ),
+        )
+        .arg(
+            Arg::with_name("inspect")
+                .long("inspect")
+                .conflicts_with_all(&["measure-only-missing", "dry-run"])
+                .help(
+                    "Prints the font's names, metrics, tables, glyph count \
+                     and axes, then how many distinct characters the input \
+                     uses and those no font has, instead of rendering. \
+                     Exits with 1 when any are missing.",
+                ),
+        )
+        .arg(
+            Arg::with_name("measure-only-missing")
+                .long("measure-only-missing")
+                .help(
+                    "Lists the lines with characters the font has no glyph \
+                     for, and which characters, instead of rendering.",
+                ),
+        )
+        .arg(
+            Arg::with_name("path-precision-adaptive")
+                .long("path-precision-adaptive")
+                .help(
+                    "Picks how many decimals path coordinates keep from the \
+                     rendered size: fewer for big text, more for small.",
+                ),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .alias("font-size")
+                .value_name("PIXELS")
+                .help(
+                    "Font size in pixels per em. Coordinates are in font units \
+                     when left out.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fallback-font")
+                .long("fallback-font")
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Font to take characters the main font doesn't have from. \
+                     Can be given more than once, they are tried in order.",
+                ),
+        )
+        .arg(
+            Arg::with_name("sort-lines")
+                .long("sort-lines")
+                .help(
+                    "Sorts the lines before rendering, so the file numbers \
+                     follow the sorted order.",
+                ),
+        )
+        .arg(
+            Arg::with_name("sort-key")
+                .long("sort-key")
+                .value_name("KEY")
+                .possible_values(&["alpha", "length"])
+                .default_value("alpha")
+                .help("What --sort-lines sorts by."),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help(
+                    "Exits with an error after rendering every line if any \
+                     character had no glyph.",
+                ),
+        )
+        .arg(
+            Arg::with_name("stdout")
+                .long("stdout")
+                .help("Prints the svgs to stdout, the same as -o -."),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .long("separator")
+                .value_name("TEXT")
+                .allow_hyphen_values(true)
+                .help(
+                    "Written between svgs printed to stdout. Nothing by \
+                     default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-padding-cells")
+                .long("glyph-padding-cells")
+                .requires("glyph-per-file")
+                .help(
+                    "Pads every glyph to the size of the largest one, so the \
+                     files of --glyph-per-file tile evenly.",
+                ),
+        )
+        .arg(
+            Arg::with_name("single-file")
+                .long("single-file")
+                .value_name("PATH")
+                .conflicts_with("glyph-per-file")
+                .help(
+                    "Stacks every line in one svg saved to PATH, a line \
+                     height apart, instead of a file per line.",
+                ),
+        )
+        .arg(
+            Arg::with_name("use-defs")
+                .long("use-defs")
+                .conflicts_with("dedupe-paths")
+                .help(
+                    "Outlines each distinct glyph once into <defs> and places \
+                     every occurrence with <use>.",
+                ),
+        )
+        .arg(
+            Arg::with_name("combine-defs")
+                .long("combine-defs")
+                .requires("single-file")
+                .conflicts_with_all(&["dedupe-paths", "merge-paths"])
+                .help(
+                    "Shares one <defs> across every line of the \
+                     --single-file document, so a glyph used on several \
+                     lines is outlined once. Implies --use-defs.",
+                ),
+        )
+        .arg(
+            Arg::with_name("advance-width-table")
+                .long("advance-width-table")
+                .value_name("FILE")
+                .help(
+                    "Writes a tab separated table of every glyph drawn and \
+                     its advance width at the rendered size.",
+                ),
+        )
+        .arg(
+            Arg::with_name("precision")
+                .long("precision")
+                .value_name("DIGITS")
+                .conflicts_with("path-precision-adaptive")
+                .help(
+                    "Rounds path coordinates to this many decimal places, \
+                     0 to 6. Coordinates are written in full by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("svg-comment")
+                .long("svg-comment")
+                .value_name("TEXT")
+                .help("Writes TEXT as an xml comment before the root element."),
+        )
+        .arg(
+            Arg::with_name("xml-indent")
+                .long("xml-indent")
+                .value_name("INDENT")
+                .help(
+                    "How elements are indented: none, which also leaves out \
+                     the line breaks, spaces:N or tabs. spaces:4 by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("xml-quotes")
+                .long("xml-quotes")
+                .value_name("QUOTES")
+                .possible_values(&["single", "double"])
+                .default_value("single")
+                .help("Quote marks around attribute values."),
+        )
+        .arg(
+            Arg::with_name("xml-declaration")
+                .long("xml-declaration")
+                .help(
+                    "Starts every svg with <?xml version=\"1.0\" \
+                     encoding=\"UTF-8\"?>.",
+                ),
+        )
+        .arg(
+            Arg::with_name("compact-paths")
+                .long("compact-paths")
+                .help(
+                    "Writes path data with relative commands and no spaces \
+                     that aren't needed, for smaller files.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-origin")
+                .long("glyph-origin")
+                .value_name("ORIGIN")
+                .possible_values(&["baseline", "center"])
+                .default_value("baseline")
+                .help(
+                    "Where glyphs are placed from: the font's origin on the \
+                     baseline, or their bounding box centered in their \
+                     advance and the line.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fill")
+                .long("fill")
+                .value_name("COLOR")
+                .help(
+                    "Fills the glyphs with COLOR, e.g. #ff0000, red or none. \
+                     Black when left out.",
+                ),
+        )
+        .arg(
+            Arg::with_name("stroke")
+                .long("stroke")
+                .value_name("COLOR")
+                .help("Outlines the glyphs in COLOR."),
+        )
+        .arg(
+            Arg::with_name("stroke-width")
+                .long("stroke-width")
+                .value_name("WIDTH")
+                .help("Width of the --stroke outline."),
+        )
+        .arg(
+            Arg::with_name("stroke-linejoin")
+                .long("stroke-linejoin")
+                .value_name("JOIN")
+                .possible_values(&["miter", "round", "bevel"])
+                .help(
+                    "How the --stroke outline turns corners. Png and pdf \
+                     output always join round.",
+                ),
+        )
+        .arg(
+            Arg::with_name("stroke-linecap")
+                .long("stroke-linecap")
+                .value_name("CAP")
+                .possible_values(&["butt", "round", "square"])
+                .help("How the --stroke outline ends open paths."),
+        )
+        .arg(
+            Arg::with_name("line-height")
+                .long("line-height")
+                .value_name("FACTOR")
+                .default_value("1")
+                .help(
+                    "Multiplies the distance between the baselines of lines \
+                     stacked by --single-file or wrapping. Below 1 they \
+                     overlap.",
+                ),
+        )
+        .arg(
+            Arg::with_name("svg-class-per-line")
+                .long("svg-class-per-line")
+                .requires("single-file")
+                .help(
+                    "Wraps each line of the --single-file document in a \
+                     group with the classes line and line-N, N numbered \
+                     like the files of a normal run, for styling with css.",
+                ),
+        )
+        .arg(
+            Arg::with_name("line-class-prefix")
+                .long("line-class-prefix")
+                .value_name("PREFIX")
+                .requires("svg-class-per-line")
+                .help(
+                    "What --svg-class-per-line's classes start with \
+                     instead of line.",
+                ),
+        )
+        .arg(
+            Arg::with_name("baseline-align-group")
+                .long("baseline-align-group")
+                .help(
+                    "Puts the baselines of lines stacked by --single-file \
+                     or wrapping on an even grid, each line spaced like the \
+                     tallest, instead of by the lines' own heights.",
+                ),
+        )
+        .arg(
+            Arg::with_name("case")
+                .long("case")
+                .value_name("CASE")
+                .possible_values(&["upper", "lower", "title", "small-caps"])
+                .help(
+                    "Maps each line's case before its glyphs are looked up. \
+                     Small caps draw lowercase letters with their capitals \
+                     at --small-caps-scale.",
+                ),
+        )
+        .arg(
+            Arg::with_name("small-caps-scale")
+                .long("small-caps-scale")
+                .value_name("FACTOR")
+                .default_value("0.75")
+                .help("How big small caps are next to the capitals."),
+        )
+        .arg(
+            Arg::with_name("line-height-from")
+                .long("line-height-from")
+                .value_name("METRIC")
+                .possible_values(&["ascent", "cap", "bbox"])
+                .default_value("ascent")
+                .help(
+                    "Measures the line height from the font's ascender and \
+                     descender, its cap height, or the glyphs' ink.",
+                ),
+        )
+        .arg(
+            Arg::with_name("letter-spacing")
+                .long("letter-spacing")
+                .value_name("AMOUNT")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .help(
+                    "Extra advance after every glyph, negative to tighten. \
+                     In pixels with --size, font units otherwise.",
+                ),
+        )
+        .arg(
+            Arg::with_name("word-spacing")
+                .long("word-spacing")
+                .value_name("AMOUNT")
+                .default_value("0")
+                .allow_hyphen_values(true)
+                .help(
+                    "Extra advance after every space, on top of the letter \
+                     spacing.",
+                ),
+        )
+        .arg(
+            Arg::with_name("face-index")
+                .long("face-index")
+                .value_name("INDEX")
+                .default_value("0")
+                .help("Which face of a .ttc or .otc collection to use."),
+        )
+        .arg(
+            Arg::with_name("list-faces")
+                .long("list-faces")
+                .help(
+                    "Prints the faces in the font with their family names, \
+                     then exits.",
+                ),
+        )
+        .arg(
+            Arg::with_name("variation")
+                .long("variation")
+                .value_name("TAG=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .help(
+                    "Sets an axis of a variable font, e.g. wght=650. Values \
+                     are clamped to the axis range. Can be repeated.",
+                ),
+        )
+        .arg(
+            Arg::with_name("list-variations")
+                .long("list-variations")
+                .help(
+                    "Prints the font's variation axes with their minimum, \
+                     default and maximum, then exits.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-not-in-font-placeholder")
+                .long("glyph-not-in-font-placeholder")
+                .alias("placeholder")
+                .value_name("CHAR")
+                .help(
+                    "Draws CHAR, which the font has to have, in place of \
+                     characters no font has instead of .notdef.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fallback-notdef-box")
+                .long("fallback-notdef-box")
+                .help(
+                    "Draws a box with a cross in place of missing \
+                     characters when the font's .notdef glyph is empty, so \
+                     they stay visible.",
+                ),
+        )
+        .arg(
+            Arg::with_name("start-index")
+                .long("start-index")
+                .value_name("N")
+                .default_value("0")
+                .help("Number the first file gets, counting up from there."),
+        )
+        .arg(
+            Arg::with_name("atomic")
+                .long("atomic")
+                .help(
+                    "Only moves the files into the output directory once \
+                     every line has been written, leaving nothing behind \
+                     if any fails.",
+                ),
+        )
+        .arg(
+            Arg::with_name("slug-max-length")
+                .long("slug-max-length")
+                .value_name("CHARS")
+                .default_value("64")
+                .help("Longest the {text} of an output name can get."),
+        )
+        .arg(
+            Arg::with_name("input-max-bytes")
+                .long("input-max-bytes")
+                .value_name("BYTES")
+                .help(
+                    "Refuses input bigger than this instead of reading all of \
+                     it into memory.",
+                ),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .conflicts_with("skip-existing")
+                .help("Overwrites files that are already there."),
+        )
+        .arg(
+            Arg::with_name("allow-collisions")
+                .long("allow-collisions")
+                .help(
+                    "Lets lines the format string gives the same name \
+                     share the file, needing --force for the later line to \
+                     replace it or --skip-existing to keep the first.",
+                ),
+        )
+        .arg(
+            Arg::with_name("skip-existing")
+                .long("skip-existing")
+                .help(
+                    "Leaves files that are already there alone and carries on \
+                     with the rest, to resume an interrupted run.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-cache-stats")
+                .long("glyph-cache-stats")
+                .help(
+                    "Prints how often a glyph's outline was already cached \
+                     and how many glyphs were, at the end.",
+                ),
+        )
+        .arg(
+            Arg::with_name("glyph-render-stats")
+                .long("glyph-render-stats")
+                .help(
+                    "Prints how many lines, glyphs and bytes were written, \
+                     and how many characters were missing, at the end.",
+                ),
+        )
+        .arg(
+            Arg::with_name("whitespace-visible")
+                .long("whitespace-visible")
+                .help(
+                    "Marks spaces with a faint dot and tabs with an arrow, \
+                     for debugging layout.",
+                ),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("N")
+                .help(
+                    "Renders this many lines at once. The number of cores by \
+                     default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("outline-simplify-tolerance")
+                .long("outline-simplify-tolerance")
+                .value_name("DISTANCE")
+                .help(
+                    "Turns curves into straight lines that stay within \
+                     DISTANCE of them, for tools without curve support.",
+                ),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .help(
+                    "Shows how far along rendering is on stderr, when it is a \
+                     terminal, and a summary at the end.",
+                ),
+        )
+        .arg(
+            Arg::with_name("merge-adjacent-identical-lines")
+                .long("merge-adjacent-identical-lines")
+                .help(
+                    "Renders a run of identical lines once, with a small \
+                     \u{d7}N for how many there were.",
+                ),
+        )
+        .arg(
+            Arg::with_name("lines")
+                .long("lines")
+                .value_name("RANGES")
+                .help(
+                    "Only renders these lines, like 1-10,42,100-. Files keep \
+                     the number they'd have had otherwise.",
+                ),
+        )
+        .arg(
+            Arg::with_name("preserve-line-numbers")
+                .long("preserve-line-numbers")
+                .help(
+                    "Numbers files by the line's place in the input even \
+                     after --sort-lines or --merge-adjacent-identical-lines \
+                     move or drop lines. --lines then goes by these numbers \
+                     too.",
+                ),
+        )
+        .arg(
+            Arg::with_name("metrics")
+                .long("metrics")
+                .value_name("FILE")
+                .conflicts_with("glyph-per-file")
+                .help(
+                    "Writes each line's width, height, ascent, descent and \
+                     glyph positions to a json file, in font units.",
+                ),
+        )
+        .arg(
+            Arg::with_name("title-from-filename")
+                .long("title-from-filename")
+                .conflicts_with_all(&["line", "benchmark", "single-file"])
+                .help(
+                    "Gives each svg a <title> with the name of the file its \
+                     line was read from, for telling where it came from. \
+                     Handy with a directory as --input.",
+                ),
+        )
+        .arg(
+            Arg::with_name("embed-text")
+                .long("embed-text")
+                .help(
+                    "Writes the line into a <title> and aria-label, so \
+                     screen readers and search can see it, and into \
+                     --output-metadata.",
+                ),
+        )
+        .arg(
+            Arg::with_name("annotate-glyphs")
+                .long("annotate-glyphs")
+                .help(
+                    "Wraps each character's path in a <g> with data-char, \
+                     data-index and data-cluster, empty for spaces, so they \
+                     line up with the text.",
+                ),
+        )
+        .arg(
+            Arg::with_name("background")
+                .long("background")
+                .value_name("COLOR")
+                .help("Puts a rectangle of COLOR behind the text."),
+        )
+        .arg(
+            Arg::with_name("padding")
+                .long("padding")
+                .value_name("AMOUNT")
+                .help(
+                    "Leaves this much room around the text, or X,Y for \
+                     different amounts sideways and up and down. Pixels with \
+                     --size, font units without.",
+                ),
+        )
+        .arg(
+            Arg::with_name("direction")
+                .long("direction")
+                .value_name("DIRECTION")
+                .possible_values(&["auto", "ltr", "rtl"])
+                .default_value("auto")
+                .help(
+                    "Which way lines run. auto goes by the first letter \
+                     with a direction, so Hebrew and Arabic lines run right \
+                     to left with any Latin and numbers in them reordered.",
+                ),
+        )
+        .arg(
+            Arg::with_name("rtl-mirror-brackets")
+                .long("rtl-mirror-brackets")
+                .help(
+                    "Mirrors every character Unicode pairs with a mirror \
+                     image in right to left runs: CJK, fullwidth and math \
+                     brackets and relations like subset of, not only the \
+                     ASCII brackets, guillemets and less or equal signs \
+                     that always are.",
+                ),
+        )
+        .arg(
+            Arg::with_name("detect-rtl-per-line")
+                .long("detect-rtl-per-line")
+                .help(
+                    "Gives each line the direction of its own first letter \
+                     with one, so Latin and Hebrew lines can mix in a file \
+                     whatever --direction says. --direction still decides \
+                     for lines without any.",
+                ),
+        )
+        .arg(
+            Arg::with_name("vertical")
+                .long("vertical")
+                .conflicts_with_all(&[
+                    "use-defs",
+                    "dedupe-paths",
+                    "single-file",
+                    "whitespace-visible",
+                    "metrics",
+                ])
+                .help(
+                    "Stacks the glyphs top to bottom in a column, using the \
+                     font's vertical metrics when it has them.",
+                ),
+        )
+        .arg(
+            Arg::with_name("canvas-width")
+                .long("canvas-width")
+                .value_name("WIDTH")
+                .conflicts_with("vertical")
+                .help(
+                    "Makes every svg this wide, pixels with --size and font \
+                     units without, with the text placed by --align.",
+                ),
+        )
+        .arg(
+            Arg::with_name("align")
+                .long("align")
+                .value_name("ALIGN")
+                .possible_values(&["left", "center", "right"])
+                .default_value("left")
+                .help(
+                    "Where text goes on a --canvas-width canvas, or lines \
+                     narrower than the widest with --single-file.",
+                ),
+        )
+        .arg(
+            Arg::with_name("tight-bounds")
+                .long("tight-bounds")
+                .conflicts_with_all(&[
+                    "vertical",
+                    "canvas-width",
+                    "max-width",
+                    "single-file",
+                ])
+                .help(
+                    "Sizes each svg to the union of its glyphs' bounding \
+                     boxes, with the ink's left edge at x = 0, instead of \
+                     the advances and the line height. Lines of only \
+                     whitespace keep their advances.",
+                ),
+        )
+        .arg(
+            Arg::with_name("shrink-to-fit")
+                .long("shrink-to-fit")
+                .requires("canvas-width")
+                .help(
+                    "Scales lines wider than --canvas-width down to fit \
+                     instead of letting them overflow.",
+                ),
+        )
+        .arg(
+            Arg::with_name("canvas-height")
+                .long("canvas-height")
+                .value_name("HEIGHT")
+                .requires("canvas-width")
+                .conflicts_with_all(&["single-file", "max-width"])
+                .help(
+                    "Makes every svg this tall as well, with the text \
+                     centered in it.",
+                ),
+        )
+        .arg(
+            Arg::with_name("canvas-fit")
+                .long("canvas-fit")
+                .value_name("FIT")
+                .possible_values(&["contain", "cover"])
+                .requires("canvas-width")
+                .conflicts_with("shrink-to-fit")
+                .help(
+                    "Scales every line to the canvas, not only ones too \
+                     wide: contain to fit entirely inside it, leaving \
+                     empty bands, cover to fill all of it and clip what \
+                     overflows. The canvas is as tall as the line without \
+                     --canvas-height.",
+                ),
+        )
+        .arg(
+            Arg::with_name("max-width")
+                .long("max-width")
+                .value_name("WIDTH")
+                .conflicts_with("vertical")
+                .help(
+                    "Wraps lines wider than this onto more lines in the \
+                     same svg, at spaces or inside words too long to fit. \
+                     Pixels with --size, font units without.",
+                ),
+        )
+        .arg(
+            Arg::with_name("underline")
+                .long("underline")
+                .conflicts_with("vertical")
+                .help("Draws a line under the text, where the font says to."),
+        )
+        .arg(
+            Arg::with_name("strikethrough")
+                .long("strikethrough")
+                .conflicts_with("vertical")
+                .help("Draws a line through the text, where the font says to."),
+        )
+        .arg(
+            Arg::with_name("palette")
+                .long("palette")
+                .value_name("INDEX")
+                .default_value("0")
+                .help(
+                    "Which of a color font's CPAL palettes its COLR glyphs \
+                     are drawn in.",
+                ),
+        )
+        .arg(
+            Arg::with_name("color-glyph-fallback")
+                .long("color-glyph-fallback")
+                .value_name("MODE")
+                .possible_values(&["mono"])
+                .help(
+                    "mono ignores a color font's COLR layers and draws each \
+                     glyph's own outline in --fill, for flat output.",
+                ),
+        )
+        .arg(
+            Arg::with_name("tab-width")
+                .long("tab-width")
+                .value_name("SPACES")
+                .default_value("4")
+                .help(
+                    "Tabs go to the next multiple of this many spaces from \
+                     the start of the line.",
+                ),
+        )
+        .arg(
+            Arg::with_name("no-normalize")
+                .long("no-normalize")
+                .help(
+                    "Leaves letters followed by combining marks as they \
+                     are, instead of using the font's precomposed glyph and \
+                     centering marks over the letter before.",
+                ),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(&["svg", "png", "pdf"])
+                .default_value("svg")
+                .help(
+                    "Writes each svg drawn into a png image, transparent \
+                     wherever --background doesn't cover, or a one page pdf \
+                     instead. With --single-file a pdf gets a page per \
+                     line. A numbered --output ends in the format's \
+                     extension.",
+                ),
+        )
+        .arg(
+            Arg::with_name("raster-scale")
+                .long("raster-scale")
+                .value_name("FACTOR")
+                .conflicts_with("dpi")
+                .help("Pixels per svg unit in pngs, 1 by default."),
+        )
+        .arg(
+            Arg::with_name("dpi")
+                .long("dpi")
+                .value_name("DPI")
+                .help(
+                    "Resolution of pngs, taking svg units as css pixels at \
+                     96 dpi.",
+                ),
+        )
+        .arg(
+            Arg::with_name("zip-compression")
+                .long("zip-compression")
+                .value_name("METHOD")
+                .possible_values(&["stored", "deflate"])
+                .help(
+                    "How files are kept in a zip output, deflate by default",
+                ),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help(
+                    "Reads options from a TOML file, keys named like the \
+                     options such as fill = \"red\", with a [variations] \
+                     table of axes. Options on the command line win.",
+                ),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help(
+                    "Reads the font and input and prints the file each line \
+                     would go to, without rendering or writing anything. \
+                     Fails where the real run would, like on a file that's \
+                     already there.",
+                ),
+        )
+        .arg(
+            Arg::with_name("merge-paths")
+                .long("merge-paths")
+                .conflicts_with_all(&[
+                    "dedupe-paths",
+                    "use-defs",
+                    "annotate-glyphs",
+                ])
+                .help(
+                    "Draws all of a line's glyphs as one path, for tools \
+                     that would treat each letter as its own object. Color \
+                     glyphs keep their own paths.",
+                ),
+        );
+    let matches = get_matches(app)?;
+    let mut font: ttf::Face<'static> = {
+        let buffer = match matches.value_of("font-base64") {
+            Some(data) => Box::new(
+                base64::decode(data.trim()).map_err(Error::FontBase64)?,
+            ),
+            None => {
+                // Checked here rather than by clap, as it can come from
+                // --config.
+                let font = matches.value_of("font").unwrap_or_else(|| {
+                    exit_usage(clap::Error::with_description(
+                        "--font or --font-base64 has to be given",
+                        ErrorKind::MissingRequiredArgument,
+                    ))
+                });
+                let open_error = |source| Error::FontOpen {
+                    path: font.to_string(),
+                    source,
+                };
+                let mut file = File::open(font).map_err(open_error)?;
+                let mut buffer = Box::new(vec![]);
+                file.read_to_end(&mut buffer).map_err(open_error)?;
+                buffer
+            }
+        };
+        // We're leaking here as the fonts are used for the whole
+        // lifetime of this program, so leaking here is an act of
+        // convenience to keep all initilization code in parse_arguments.
+        let buffer = buffer.leak();
+        // Plain fonts aren't collections, but they are their face 0.
+        let count = ttf::fonts_in_collection(buffer).unwrap_or(1);
+        if matches.is_present("list-faces") {
+            list_faces(buffer, count);
+            std::process::exit(0);
+        }
+        let index = matches.value_of("face-index").unwrap();
+        let index: u32 = index
+            .parse()
+            .map_err(|_| invalid_option("face index", index))?;
+        if index >= count {
+            return Err(Error::FaceIndex { index, count });
+        }
+        ttf::Face::from_slice(buffer, index).map_err(Error::FontParse)?
+    };
+    if matches.is_present("list-variations") {
+        list_variations(&font);
+        std::process::exit(0);
+    }
+    for variation in matches.values_of("variation").into_iter().flatten() {
+        let (tag, value) = variation
+            .split_once('=')
+            .and_then(|(tag, value)| Some((tag, value.parse::<f32>().ok()?)))
+            .filter(|(tag, _)| (1..=4).contains(&tag.len()) && tag.is_ascii())
+            .ok_or_else(|| invalid_option("variation", variation))?;
+        let tag = ttf::Tag::from_bytes_lossy(tag.as_bytes());
+        match font
+            .variation_axes()
+            .into_iter()
+            .find(|axis| axis.tag == tag)
+        {
+            Some(axis) => {
+                let value = value.clamp(axis.min_value, axis.max_value);
+                font.set_variation(tag, value);
+            }
+            None => eprintln!(
+                "{}the font has no {} axis, ignoring --variation {}",
+                YELLOW_WARNING, tag, variation
+            ),
+        }
+    }
+    let mut faces = vec![font];
+    for path in matches.values_of("fallback-font").into_iter().flatten() {
+        let open_error = |source| Error::FontOpen {
+            path: path.to_string(),
+            source,
+        };
+        let buffer = std::fs::read(path).map_err(open_error)?.leak();
+        faces.push(ttf::Face::from_slice(buffer, 0).map_err(Error::FontParse)?);
+    }
+
+    let max_bytes: Option<u64> = match matches.value_of("input-max-bytes") {
+        Some(max) => match max.parse() {
+            Ok(max) => Some(max),
+            Err(_) => return Err(invalid_option("input size", max)),
+        },
+        None => None,
+    };
+    let read_input = |input: &str| -> Result<String, Error> {
+        let path = if input == "--" { "stdin" } else { input };
+        let read_error = |source| Error::InputRead {
+            path: path.to_string(),
+            source,
+        };
+        let reader: Box<dyn Read> = if input == "--" {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(input).map_err(read_error)?)
+        };
+        // One byte past the limit is enough to know it was gone over.
+        let limit = max_bytes.map_or(u64::MAX, |max| max.saturating_add(1));
+        let mut buffer = vec![];
+        reader
+            .take(limit)
+            .read_to_end(&mut buffer)
+            .map_err(read_error)?;
+        if let Some(max) = max_bytes {
+            if buffer.len() as u64 > max {
+                return Err(Error::InputTooLarge {
+                    path: path.to_string(),
+                    max,
+                });
+            }
+        }
+        String::from_utf8(buffer).map_err(|_| Error::InputEncoding {
+            path: path.to_string(),
+        })
+    };
+    let plain = |text: &str| InputLine {
+        // Whatever lines() leaves of a CRLF ending, like on the last line.
+        text: text.strip_suffix('\r').unwrap_or(text).to_string(),
+        columns: vec![],
+        repeats: 1,
+        index: 0,
+        file: 0,
+        file_index: 0,
+        fill: None,
+        size: None,
+    };
+    let continue_on_error = matches.is_present("continue-on-error");
+    let mut files = vec![];
+    let mut titles = vec![];
+    let mut input_errors = vec![];
+    let benchmark = matches.value_of("benchmark");
+    let mut lines: Vec<InputLine> = if let Some(iterations) = benchmark {
+        match iterations.parse() {
+            Ok(0) | Err(_) => {
+                return Err(invalid_option("benchmark", iterations))
+            }
+            Ok(iterations) => {
+                (0..iterations).map(|_| plain(TEST_STRING)).collect()
+            }
+        }
+    } else if let Some(lines) = matches.values_of("line") {
+        lines.map(plain).collect()
+    } else if let Some(path) = matches.value_of("input-lines-from-args-file") {
+        let text = read_input(path)?;
+        let read =
+            args_file::parse(&text).map_err(|message| Error::Config {
+                path: path.to_string(),
+                message,
+            })?;
+        files.push(
+            Path::new(path).file_stem().map_or(String::new(), |stem| {
+                stem.to_string_lossy().into_owned()
+            }),
+        );
+        let mut lines = vec![];
+        for args_file::Line { text, fill, size } in read {
+            let fill = parse_color("fill", fill.as_deref())?;
+            if let Some(size) = size.filter(|size| *size <= 0.0) {
+                return Err(invalid_option("size", &size.to_string()));
+            }
+            lines.push(InputLine {
+                fill,
+                size,
+                ..plain(&text)
+            });
+        }
+        lines
+    } else {
+        let mut inputs = vec![];
+        for pattern in matches.values_of("text").unwrap() {
+            match expand_input(pattern) {
+                Ok(paths) => inputs.extend(paths),
+                Err(e) if continue_on_error => input_errors.push(e),
+                Err(e) => return Err(e),
+            }
+        }
+        let mut lines = vec![];
+        for input in inputs {
+            let input = input.to_string_lossy();
+            let text = match read_input(&input) {
+                Ok(text) => text,
+                Err(e) if continue_on_error => {
+                    input_errors.push(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let file = files.len();
+            if matches.is_present("title-from-filename") {
+                titles.push(match input.as_ref() {
+                    "--" => "stdin".to_string(),
+                    input => Path::new(input)
+                        .file_name()
+                        .map_or(String::new(), |name| {
+                            name.to_string_lossy().into_owned()
+                        }),
+                });
+            }
+            files.push(match input.as_ref() {
+                "--" => "stdin".to_string(),
+                input => {
+                    Path::new(input).file_stem().map_or(String::new(), |stem| {
+                        stem.to_string_lossy().into_owned()
+                    })
+                }
+            });
+            let read = match matches.value_of("input-format") {
+                Some("plain") if matches.is_present("no-newline-split") => {
+                    vec![plain(text.strip_suffix('\n').unwrap_or(&text))]
+                }
+                Some("plain") => text.lines().map(plain).collect(),
+                format => {
+                    let delimiter =
+                        if format == Some("csv") { ',' } else { '\t' };
+                    let column = matches.value_of("text-column").unwrap();
+                    let column = match column.parse::<usize>() {
+                        Ok(column) if column > 0 => column,
+                        _ => return Err(invalid_option("text column", column)),
+                    };
+                    parse_delimited(&text, delimiter)
+                        .into_iter()
+                        .enumerate()
+                        .map(|(row, columns)| {
+                            let text = columns
+                                .get(column - 1)
+                                .ok_or(Error::MissingColumn {
+                                    row: row + 1,
+                                    column,
+                                })?
+                                .clone();
+                            Ok(InputLine {
+                                text,
+                                columns,
+                                ..plain("")
+                            })
+                        })
+                        .collect::<Result<_, Error>>()?
+                }
+            };
+            lines.extend(
+                read.into_iter().map(|line| InputLine { file, ..line }),
+            );
+        }
+        lines
+    };
+    if files.is_empty() {
+        files.push(String::new());
+    }
+    let number_lines = |lines: &mut [InputLine]| {
+        let mut counts = vec![0; files.len()];
+        for (index, line) in lines.iter_mut().enumerate() {
+            line.index = index;
+            line.file_index = counts[line.file];
+            counts[line.file] += 1;
+        }
+    };
+    // Numbered as read, before sorting or merging moves them.
+    let preserve_numbers = matches.is_present("preserve-line-numbers");
+    if preserve_numbers {
+        number_lines(&mut lines);
+    }
+    if matches.is_present("input-escapes") {
+        for line in &mut lines {
+            line.text = unescape_codepoints(&line.text);
+        }
+    }
+    if matches.is_present("input-trim") {
+        for line in &mut lines {
+            line.text = line.text.trim().to_string();
+        }
+    }
+    if matches.is_present("collapse-whitespace") {
+        for line in &mut lines {
+            let mut collapsed = String::with_capacity(line.text.len());
+            for c in line.text.chars() {
+                if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+            }
+            line.text = collapsed;
+        }
+    }
+    if matches.is_present("sort-lines") {
+        if matches.value_of("sort-key") == Some("length") {
+            lines.sort_by_key(|line| line.text.chars().count());
+        } else {
+            lines.sort_by(|a, b| a.text.cmp(&b.text));
+        }
+    }
+    if matches.is_present("merge-adjacent-identical-lines") {
+        let mut merged: Vec<InputLine> = Vec::with_capacity(lines.len());
+        for line in lines {
+            match merged.last_mut() {
+                Some(last)
+                    if last.text == line.text
+                        && last.file == line.file
+                        && last.fill == line.fill
+                        && last.size == line.size =>
+                {
+                    last.repeats += 1
+                }
+                _ => merged.push(line),
+            }
+        }
+        lines = merged;
+    }
+    if !preserve_numbers {
+        number_lines(&mut lines);
+    }
+    if let Some(ranges) = matches.value_of("lines") {
+        let ranges = parse_ranges(ranges)?;
+        lines.retain(|line| {
+            ranges.iter().any(|&(start, end)| {
+                line.index + 1 >= start
+                    && end.is_none_or(|end| line.index < end)
+            })
+        });
+    }
+
+    let shard_size: Option<usize> = match matches.value_of("shard-size") {
+        Some(size) => match size.parse() {
+            Ok(0) | Err(_) => return Err(invalid_option("shard size", size)),
+            Ok(size) => Some(size),
+        },
+        None => None,
+    };
+
+    let format = match matches.value_of("format").unwrap() {
+        "png" => {
+            let (option, value) = match matches.value_of("dpi") {
+                Some(dpi) => ("dpi", dpi),
+                None => (
+                    "raster scale",
+                    matches.value_of("raster-scale").unwrap_or("1"),
+                ),
+            };
+            let scale = match value.parse::<f64>() {
+                Ok(scale) if scale > 0.0 && scale.is_finite() => scale,
+                _ => return Err(invalid_option(option, value)),
+            };
+            Format::Png(if option == "dpi" { scale / 96.0 } else { scale })
+        }
+        "pdf" => Format::Pdf,
+        _ => Format::Svg,
+    };
+    let extension = match format {
+        Format::Svg => "svg",
+        Format::Png(_) => "png",
+        Format::Pdf => "pdf",
+    };
+    if format != Format::Svg && matches.value_of("svg-root-tag") != Some("svg")
+    {
+        return Err(Error::InvalidOutput(format!(
+            "--format {} needs an svg root tag.",
+            extension
+        )));
+    }
+    let overwrite = if matches.is_present("force") {
+        Overwrite::Force
+    } else if matches.is_present("skip-existing") {
+        Overwrite::Skip
+    } else {
+        Overwrite::Refuse
+    };
+    let allow_collisions = matches.is_present("allow-collisions");
+    if allow_collisions && overwrite == Overwrite::Refuse {
+        return Err(Error::InvalidOutput(
+            "--allow-collisions needs --force or --skip-existing to say \
+             which line's file is kept."
+                .to_string(),
+        ));
+    }
+    let compression = match matches.value_of("zip-compression") {
+        Some("stored") => Compression::Stored,
+        _ => Compression::Deflate,
+    };
+    let zip = |format, path| {
+        Archive::new(format, path, shard_size, compression, overwrite)
+            .map(Output::Zip)
+    };
+    let mut per_file = false;
+    let mut output: Output = {
+        let output = matches.value_of("output").unwrap();
+        let mut path = PathBuf::from(output);
+
+        if let Some(single_file) = matches.value_of("single-file") {
+            if path.extension().is_some_and(|ext| ext == extension) {
+                return Err(Error::InvalidOutput(
+                    "--single-file can't be used with a numbered format \
+                     string."
+                        .to_string(),
+                ));
+            }
+            Output::File(PathBuf::from(single_file), overwrite)
+        } else if output == "base64:-" {
+            Output::Base64Stdout
+        } else if output == "-" || matches.is_present("stdout") {
+            Output::Stdout {
+                separator: matches
+                    .value_of("separator")
+                    .unwrap_or_default()
+                    .to_string(),
+                written: 0,
+            }
+        } else {
+            match path.extension().map(|ext| ext.to_str()) {
+                Some(Some("svg")) | Some(Some("png")) | Some(Some("pdf")) => {
+                    let format = {
+                        let file = path
+                            .file_name()
+                            .ok_or_else(|| {
+                                Error::InvalidOutput(
+                                    "Path has no name.".to_string(),
+                                )
+                            })?
+                            .to_str()
+                            .unwrap();
+                        let bad_format = || {
+                            Error::InvalidOutput(format!(
+                                "output not formatted correctly. Use {{}} or \
+                                 a zero padded {{:03}} for the number, \
+                                 {{text}} for the line, {{col2}} for a \
+                                 column and {{file}} for the input file, \
+                                 ending in .{}.",
+                                extension
+                            ))
+                        };
+                        let (_, mut pieces) = parse_file(file, extension)
+                            .map_err(|_| bad_format())?;
+                        let max_length =
+                            matches.value_of("slug-max-length").unwrap();
+                        let max_length = match max_length.parse() {
+                            Ok(0) | Err(_) => {
+                                return Err(invalid_option(
+                                    "slug length",
+                                    max_length,
+                                ))
+                            }
+                            Ok(max_length) => max_length,
+                        };
+                        for piece in &mut pieces {
+                            if let Piece::Slug(length) = piece {
+                                *length = max_length;
+                            }
+                        }
+                        if pieces
+                            .iter()
+                            .all(|piece| matches!(piece, Piece::Text(_)))
+                        {
+                            return Err(bad_format());
+                        }
+                        per_file = pieces
+                            .iter()
+                            .any(|piece| matches!(piece, Piece::File));
+                        FormatString { pieces, extension }
+                    };
+
+                    path.pop();
+                    // A format string inside an archive names its entries.
+                    if path.extension().is_some_and(|ext| ext == "zip") {
+                        zip(format, path)?
+                    } else {
+                        Output::Directory(Directory::new(
+                            format, path, shard_size,
+                        ))
+                    }
+                }
+                Some(Some("zip")) => zip(
+                    FormatString {
+                        extension,
+                        ..Default::default()
+                    },
+                    path,
+                )?,
+                Some(Some(ext)) => {
+                    return Err(Error::InvalidOutput(format!(
+                        "{} is not a valid output type.",
+                        ext
+                    )))
+                }
+                Some(None) => {
+                    return Err(Error::InvalidOutput(
+                        "extention is not utf8 formatted.".to_string(),
+                    ))
+                }
+                None => Output::Directory(Directory::new(
+                    FormatString {
+                        extension,
+                        ..Default::default()
+                    },
+                    path,
+                    shard_size,
+                )),
+            }
+        }
+    };
+    if let Output::Directory(directory) = &mut output {
+        directory.overwrite = overwrite;
+    }
+    if matches.is_present("zip-compression")
+        && !matches!(output, Output::Zip(_))
+    {
+        return Err(Error::InvalidOutput(
+            "--zip-compression only works when writing a zip.".to_string(),
+        ));
+    }
+    let dry_run = matches.is_present("dry-run");
+    if matches.is_present("atomic") {
+        match &mut output {
+            // Staging makes a directory, which a dry run mustn't.
+            Output::Directory(_) if dry_run => {}
+            Output::Directory(directory) => directory.stage()?,
+            _ => {
+                return Err(Error::InvalidOutput(
+                    "--atomic only works when writing to a directory."
+                        .to_string(),
+                ))
+            }
+        }
+    }
+    let baseline_offset: f32 = {
+        let offset = matches.value_of("baseline-offset").unwrap();
+        offset
+            .parse()
+            .map_err(|_| invalid_option("baseline offset", offset))?
+    };
+    let advance_scale: f32 = {
+        let scale = matches.value_of("glyph-advance-scale").unwrap();
+        scale
+            .parse()
+            .map_err(|_| invalid_option("advance scale", scale))?
+    };
+    let embolden: f32 = {
+        let amount = matches.value_of("embolden").unwrap();
+        amount
+            .parse()
+            .map_err(|_| invalid_option("embolden amount", amount))?
+    };
+    let size: Option<f32> = match matches.value_of("size") {
+        Some(size) => match size.parse::<f32>() {
+            Ok(size) if size > 0.0 => Some(size),
+            _ => return Err(invalid_option("size", size)),
+        },
+        None => None,
+    };
+    let fill = parse_color("fill", matches.value_of("fill"))?;
+    let stroke = parse_color("stroke", matches.value_of("stroke"))?;
+    let stroke_width: Option<f32> = match matches.value_of("stroke-width") {
+        Some(width) => match width.parse::<f32>() {
+            Ok(width) if width >= 0.0 => Some(width),
+            _ => return Err(invalid_option("stroke width", width)),
+        },
+        None => None,
+    };
+    let canvas_width: Option<f32> = match matches.value_of("canvas-width") {
+        Some(width) => match width.parse::<f32>() {
+            Ok(width) if width > 0.0 && width.is_finite() => Some(width),
+            _ => return Err(invalid_option("canvas width", width)),
+        },
+        None => None,
+    };
+    let canvas_height: Option<f32> = match matches.value_of("canvas-height") {
+        Some(height) => match height.parse::<f32>() {
+            Ok(height) if height > 0.0 && height.is_finite() => Some(height),
+            _ => return Err(invalid_option("canvas height", height)),
+        },
+        None => None,
+    };
+    let max_width: Option<f32> = match matches.value_of("max-width") {
+        Some(width) => match width.parse::<f32>() {
+            Ok(width) if width > 0.0 && width.is_finite() => Some(width),
+            _ => return Err(invalid_option("max width", width)),
+        },
+        None => None,
+    };
+    let tab_width = matches.value_of("tab-width").unwrap();
+    let tab_width = tab_width
+        .parse::<usize>()
+        .map_err(|_| invalid_option("tab width", tab_width))?;
+    let palette = matches.value_of("palette").unwrap();
+    let palette = match palette.parse::<usize>() {
+        Ok(index) if index < color::palette_count(&faces[0]).max(1) => index,
+        _ => return Err(invalid_option("palette", palette)),
+    };
+    let background = parse_color("background", matches.value_of("background"))?;
+    let padding = match matches.value_of("padding") {
+        Some(padding) => {
+            let amount = |amount: &str| match amount.trim().parse::<f32>() {
+                Ok(amount) if amount >= 0.0 && amount.is_finite() => Ok(amount),
+                _ => Err(invalid_option("padding", padding)),
+            };
+            match padding.split_once(',') {
+                Some((x, y)) => (amount(x)?, amount(y)?),
+                None => (amount(padding)?, amount(padding)?),
+            }
+        }
+        None => (0.0, 0.0),
+    };
+    let comment = match matches.value_of("svg-comment") {
+        // Comments can't hold a double hyphen.
+        Some(comment) if comment.contains("--") => {
+            return Err(invalid_option("svg comment", comment))
+        }
+        comment => comment.map(|comment| comment.to_string()),
+    };
+    let line_class = match matches.value_of("line-class-prefix") {
+        _ if !matches.is_present("svg-class-per-line") => None,
+        Some(prefix)
+            if prefix.is_empty() || prefix.contains(char::is_whitespace) =>
+        {
+            return Err(invalid_option("line class prefix", prefix));
+        }
+        prefix => Some(prefix.unwrap_or("line").to_string()),
+    };
+    let line_height_factor = matches.value_of("line-height").unwrap();
+    let line_height_factor = match line_height_factor.parse::<f32>() {
+        Ok(factor) if factor > 0.0 && factor.is_finite() => factor,
+        _ => return Err(invalid_option("line height", line_height_factor)),
+    };
+    let small_caps_scale = matches.value_of("small-caps-scale").unwrap();
+    let small_caps_scale = match small_caps_scale.parse::<f32>() {
+        Ok(scale) if scale > 0.0 && scale.is_finite() => scale,
+        _ => return Err(invalid_option("small caps scale", small_caps_scale)),
+    };
+    let indent = match matches.value_of("xml-indent") {
+        None => Indent::Spaces(4),
+        Some("none") => Indent::None,
+        Some("tabs") => Indent::Tabs,
+        Some(indent) => indent
+            .strip_prefix("spaces:")
+            .and_then(|spaces| spaces.parse().ok())
+            .map(Indent::Spaces)
+            .ok_or_else(|| invalid_option("xml indent", indent))?,
+    };
+    // Pixels with --size, font units without.
+    let spacing = |option: &'static str| -> Result<f32, Error> {
+        let amount = matches.value_of(option).unwrap();
+        let amount: f32 =
+            amount.parse().map_err(|_| invalid_option(option, amount))?;
+        Ok(match size {
+            Some(size) => amount * faces[0].units_per_em() as f32 / size,
+            None => amount,
+        })
+    };
+    let letter_spacing = spacing("letter-spacing")?;
+    let word_spacing = spacing("word-spacing")?;
+    let placeholder = match matches.value_of("glyph-not-in-font-placeholder") {
+        Some(placeholder) => {
+            let mut chars = placeholder.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if lookup(&faces, c).is_some() => Some(c),
+                _ => return Err(invalid_option("placeholder", placeholder)),
+            }
+        }
+        None => None,
+    };
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => match jobs.parse() {
+            Ok(0) | Err(_) => return Err(invalid_option("job count", jobs)),
+            Ok(jobs) => jobs,
+        },
+        None => std::thread::available_parallelism().map_or(1, usize::from),
+    };
+    let flatten_tolerance: Option<f32> =
+        match matches.value_of("outline-simplify-tolerance") {
+            Some(tolerance) => match tolerance.parse::<f32>() {
+                Ok(tolerance) if tolerance > 0.0 => Some(tolerance),
+                _ => return Err(invalid_option("tolerance", tolerance)),
+            },
+            None => None,
+        };
+    let start_index = matches.value_of("start-index").unwrap();
+    let start_index: usize = start_index
+        .parse()
+        .map_err(|_| invalid_option("start index", start_index))?;
+    let precision: Option<i32> = match matches.value_of("precision") {
+        Some(digits) => match digits.parse::<i32>() {
+            Ok(digits) if (0..=6).contains(&digits) => Some(digits),
+            _ => return Err(invalid_option("precision", digits)),
+        },
+        None => None,
+    };
+    let max_height: Option<f32> = match matches.value_of("max-height") {
+        Some(height) => match height.parse::<f32>() {
+            Ok(height) if height > 0.0 => Some(height),
+            _ => return Err(invalid_option("max height", height)),
+        },
+        None => None,
+    };
+    let template = match matches.value_of("template") {
+        Some(path) => {
+            let template = std::fs::read_to_string(path).map_err(|source| {
+                Error::InputRead {
+                    path: path.to_string(),
+                    source,
+                }
+            })?;
+            let anchor =
+                matches.value_of("template-anchor").unwrap_or("text-slot");
+            let fit = matches.is_present("fit-anchor");
+            let template =
+                Template::new(template, anchor, fit).map_err(|message| {
+                    Error::Template {
+                        path: path.to_string(),
+                        message,
+                    }
+                })?;
+            Some(template)
+        }
+        None => None,
+    };
+    let contour_limit: Option<usize> =
+        match matches.value_of("contour-count-limit") {
+            Some(limit) => {
+                Some(limit.parse().map_err(|_| {
+                    invalid_option("contour count limit", limit)
+                })?)
+            }
+            None => None,
+        };
+    let max_advance: Option<f32> = match matches.value_of("max-advance-ratio") {
+        Some(ratio) => match ratio.parse::<f32>() {
+            Ok(ratio) if ratio > 0.0 => {
+                Some(ratio * average_advance(&faces[0]))
+            }
+            _ => return Err(invalid_option("max advance ratio", ratio)),
+        },
+        None => None,
+    };
+    let metadata = if matches.is_present("output-metadata")
+        && !matches.is_present("no-metadata")
+    {
+        Some(Metadata {
+            font_family: family_name(&faces[0]).unwrap_or_default(),
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+        })
+    } else {
+        None
+    };
+    Ok(Args {
+        faces,
+        lines,
+        files,
+        titles,
+        per_file,
+        input_errors,
+        output,
+        options: RenderOptions {
+            baseline_offset,
+            glyph_per_file: matches.is_present("glyph-per-file"),
+            advance_scale,
+            bbox_fallback: matches.is_present("glyph-bbox-fallback"),
+            embolden,
+            letter_spacing,
+            word_spacing,
+            metadata,
+            embed_text: matches.is_present("embed-text"),
+            annotate_glyphs: matches.is_present("annotate-glyphs"),
+            background,
+            padding,
+            direction: match matches.value_of("direction") {
+                Some("ltr") => Direction::Ltr,
+                Some("rtl") => Direction::Rtl,
+                _ => Direction::Auto,
+            },
+            direction_per_line: matches.is_present("detect-rtl-per-line"),
+            mirror_brackets: matches.is_present("rtl-mirror-brackets"),
+            vertical: matches.is_present("vertical"),
+            canvas_width,
+            align: match matches.value_of("align") {
+                Some("center") => Align::Center,
+                Some("right") => Align::Right,
+                _ => Align::Left,
+            },
+            shrink_to_fit: matches.is_present("shrink-to-fit"),
+            canvas_height,
+            canvas_fit: match matches.value_of("canvas-fit") {
+                Some("contain") => Some(CanvasFit::Contain),
+                Some("cover") => Some(CanvasFit::Cover),
+                _ => None,
+            },
+            max_width,
+            underline: matches.is_present("underline"),
+            strikethrough: matches.is_present("strikethrough"),
+            palette,
+            mono_color_glyphs: matches.value_of("color-glyph-fallback")
+                == Some("mono"),
+            tab_width,
+            normalize: !matches.is_present("no-normalize"),
+            max_height,
+            max_advance,
+            round_trip_test: matches.is_present("round-trip-test"),
+            transform: matches.value_of("transform").map(str::to_string),
+            optimize_paths: matches.is_present("optimize-paths"),
+            gdef_spacing: matches.is_present("glyph-spacing-from-metrics"),
+            kerning_source: match matches.value_of("kerning-source") {
+                Some("kern") => KerningSource::Kern,
+                Some("gpos") => KerningSource::Gpos,
+                Some("both") => KerningSource::Both,
+                _ => KerningSource::Auto,
+            },
+            svg_version: matches.value_of("svg-version").map(str::to_string),
+            fix_winding: matches.is_present("glyph-winding-fix-auto"),
+            emit_baseline: matches.is_present("emit-baseline-path"),
+            visual_order: matches.value_of("glyph-order") == Some("visual"),
+            fit_to_square: matches.is_present("fit-to-square"),
+            contour_limit,
+            dedupe_paths: matches.is_present("dedupe-paths"),
+            merge_paths: matches.is_present("merge-paths"),
+            compact_paths: matches.is_present("compact-paths"),
+            flatten_tolerance,
+            use_defs: matches.is_present("use-defs")
+                || matches.is_present("combine-defs"),
+            size,
+            tight_bounds: matches.is_present("tight-bounds"),
+            line_height_factor,
+            baseline_grid: matches.is_present("baseline-align-group"),
+            line_class,
+            case: match matches.value_of("case") {
+                Some("upper") => Some(Case::Upper),
+                Some("lower") => Some(Case::Lower),
+                Some("title") => Some(Case::Title),
+                Some("small-caps") => Some(Case::SmallCaps),
+                _ => None,
+            },
+            small_caps_scale,
+            adaptive_precision: matches.is_present("path-precision-adaptive"),
+            precision,
+            comment,
+            indent,
+            double_quotes: matches.value_of("xml-quotes") == Some("double"),
+            xml_declaration: matches.is_present("xml-declaration"),
+            fill,
+            stroke,
+            stroke_width,
+            stroke_linejoin: matches
+                .value_of("stroke-linejoin")
+                .map(str::to_string),
+            stroke_linecap: matches
+                .value_of("stroke-linecap")
+                .map(str::to_string),
+            crisp_edges: matches.value_of("antialias-hint") == Some("none"),
+            line_height: match matches.value_of("line-height-from") {
+                Some("cap") => LineHeight::Cap,
+                Some("bbox") => LineHeight::Bbox,
+                _ => LineHeight::Ascent,
+            },
+            placeholder,
+            notdef_box: matches.is_present("fallback-notdef-box"),
+            whitespace_visible: matches.is_present("whitespace-visible"),
+            glyph_origin: match matches.value_of("glyph-origin") {
+                Some("center") => GlyphOrigin::Center,
+                _ => GlyphOrigin::Baseline,
+            },
+            root_tag: match matches.value_of("svg-root-tag") {
+                Some("symbol") => RootTag::Symbol,
+                Some("g") => RootTag::G,
+                _ => RootTag::Svg,
+            },
+            ..Default::default()
+        },
+        continue_on_error,
+        glyph_index_map: matches.value_of("glyph-index-map").map(PathBuf::from),
+        advance_width_table: matches
+            .value_of("advance-width-table")
+            .map(PathBuf::from),
+        metrics: matches.value_of("metrics").map(PathBuf::from),
+        benchmark: benchmark.is_some(),
+        measure_missing: matches.is_present("measure-only-missing"),
+        inspect: matches.is_present("inspect"),
+        template,
+        preview: matches.is_present("preview"),
+        bbox_union_report: matches.is_present("glyph-bbox-union-report"),
+        strict: matches.is_present("strict"),
+        glyph_padding_cells: matches.is_present("glyph-padding-cells"),
+        single_file: matches.is_present("single-file"),
+        start_index,
+        glyph_render_stats: matches.is_present("glyph-render-stats"),
+        glyph_cache_stats: matches.is_present("glyph-cache-stats"),
+        jobs,
+        progress: matches.is_present("progress"),
+        format,
+        dry_run,
+        allow_collisions,
+    })
+}
+
+/// Replaces `\u{1F600}` and `U+0041` style escapes with the characters they
+/// name. Anything that isn't a valid code point is left as written.
+fn unescape_codepoints(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let escape = if let Some(braced) = rest.strip_prefix("\\u{") {
+            braced
+                .find('}')
+                .filter(|&end| (1..=6).contains(&end))
+                .map(|end| (&braced[..end], "\\u{}".len() + end))
+        } else if let Some(digits) = rest.strip_prefix("U+") {
+            let end = digits
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(digits.len())
+                .min(6);
+            Some((&digits[..end], "U+".len() + end)).filter(|_| end >= 4)
+        } else {
+            None
+        };
+        let escaped = escape.and_then(|(hex, len)| {
+            let c =
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)?;
+            Some((c, len))
+        });
+        match escaped {
+            Some((c, len)) => {
+                unescaped.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                unescaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    unescaped
+}
+
+/// Opens the file in the system's default viewer. Nothing happens on CI or,
+/// outside of Windows and macOS, without a display to show it on.
+fn open_preview(path: &std::path::Path) {
+    let headless = std::env::var_os("CI").is_some()
+        || (cfg!(all(unix, not(target_os = "macos")))
+            && std::env::var_os("DISPLAY").is_none()
+            && std::env::var_os("WAYLAND_DISPLAY").is_none());
+    if headless {
+        return;
+    }
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    if let Err(e) = command.arg(path).spawn() {
+        eprintln!(
+            "{}could not open {} for preview: {}",
+            YELLOW_WARNING,
+            path.display(),
+            e
+        );
+    }
+}
+
+/// The characters of the text that need a glyph, so without blanks.
+fn drawn_chars(
+    faces: &[ttf::Face],
+    text: &str,
+    options: &RenderOptions,
+) -> Vec<char> {
+    let text = cased(text, options);
+    let chars = if options.normalize {
+        // Decomposed letters the font has precomposed are drawn with those.
+        compose(&text, |c| lookup(faces, c).is_some())
+    } else {
+        text.chars().collect()
+    };
+    chars.into_iter().filter(|&c| !is_blank(c)).collect()
+}
+
+/// The text as --case maps it, for what looks its glyphs up a character at
+/// a time. Small caps give the capitals.
+fn cased<'t>(text: &'t str, options: &RenderOptions) -> Cow<'t, str> {
+    match options.case {
+        Some(case) => Cow::Owned(case.apply(text)),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Characters of the text no font has a glyph for, each listed once.
+fn missing_chars(
+    faces: &[ttf::Face],
+    text: &str,
+    options: &RenderOptions,
+) -> Vec<char> {
+    let mut missing: Vec<char> = vec![];
+    for c in drawn_chars(faces, text, options) {
+        if lookup(faces, c).is_none() && !missing.contains(&c) {
+            missing.push(c);
+        }
+    }
+    missing
+}
+
+/// Lists characters along with their code points, e.g. `'é' (U+00E9)`.
+fn describe_chars(chars: &[char]) -> String {
+    chars
+        .iter()
+        .map(|c| format!("'{}' (U+{:04X})", c.escape_debug(), *c as u32))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints how many faces the font has and each one's family name.
+fn list_faces(font: &[u8], count: u32) {
+    println!("{} face(s)", count);
+    for index in 0..count {
+        let family = match ttf::Face::from_slice(font, index) {
+            Ok(face) => family_name(&face).unwrap_or_default(),
+            Err(e) => format!("({})", e),
+        };
+        println!("{}\t{}", index, family);
+    }
+}
+
+/// Parses 1-based inclusive ranges like `1-10,42,100-`, an open end is
+/// `None`.
+fn parse_ranges(ranges: &str) -> Result<Vec<(usize, Option<usize>)>, Error> {
+    let invalid = || invalid_option("line range", ranges);
+    let number = |number: &str| match number.trim().parse::<usize>() {
+        Ok(0) | Err(_) => Err(invalid()),
+        Ok(number) => Ok(number),
+    };
+    ranges
+        .split(',')
+        .map(|range| {
+            let (start, end) = match range.split_once('-') {
+                Some((start, "")) => (number(start)?, None),
+                Some((start, end)) => (number(start)?, Some(number(end)?)),
+                None => (number(range)?, Some(number(range)?)),
+            };
+            if end.is_some_and(|end| end < start) {
+                return Err(invalid());
+            }
+            Ok((start, end))
+        })
+        .collect()
+}
+
+/// Puts a small "×N" in the top right corner, inside the root element.
+fn annotate_repeats(
+    mut svg: String,
+    repeats: usize,
+    size: (f32, f32),
+    options: &RenderOptions,
+) -> String {
+    let font_size = size.1 * 0.3;
+    let mut annotation = format!(
+        "<text x='{}' y='{}' font-size='{}' text-anchor='end'>\u{d7}{}</text>",
+        size.0, font_size, font_size, repeats
+    );
+    if options.double_quotes {
+        annotation = annotation.replace('\'', "\"");
+    }
+    if let Some(end) = svg.rfind("</") {
+        svg.insert_str(end, &annotation);
+    }
+    svg
+}
+
+/// Renders the lines spread over `jobs` threads, each titled after its
+/// input file when there are titles. The svgs come back in the lines'
+/// order.
+/// The options with the line's own fill and size in place of the ones
+/// given for every line.
+fn line_options<'a>(
+    options: &'a RenderOptions,
+    line: &InputLine,
+) -> Cow<'a, RenderOptions> {
+    if line.fill.is_none() && line.size.is_none() {
+        return Cow::Borrowed(options);
+    }
+    Cow::Owned(RenderOptions {
+        fill: line.fill.clone().or_else(|| options.fill.clone()),
+        size: line.size.or(options.size),
+        ..options.clone()
+    })
+}
+
+fn render_lines<'a>(
+    faces: &'a [ttf::Face<'a>],
+    lines: &[InputLine],
+    titles: &[String],
+    options: &RenderOptions,
+    jobs: usize,
+) -> Vec<(Vec<Glyph<'a>>, String, (f32, f32))> {
+    let render_line = |line: &InputLine| {
+        let options = &*line_options(options, line);
+        let glyphs = glyphs(faces, &line.text, options);
+        let id = format!("line-{}", line.index + 1);
+        let title = titles.get(line.file).map(String::as_str);
+        let (svg, size) =
+            render_titled(&faces[0], &glyphs, options, &id, &line.text, title);
+        (glyphs, svg, size)
+    };
+    let render_line = &render_line;
+    let chunk_size = lines.len().div_ceil(jobs).max(1);
+    std::thread::scope(|scope| {
+        let chunks = lines
+            .chunks(chunk_size)
+            .map(|lines| {
+                scope.spawn(move || {
+                    lines.iter().map(render_line).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.join().unwrap())
+            .collect()
+    })
+}
+
+/// Prints what the primary font is and how many of the characters the
+/// lines use no font has, returning whether that's none.
+fn report_inspection(
+    faces: &[ttf::Face],
+    lines: &[InputLine],
+    options: &RenderOptions,
+) -> bool {
+    let face = &faces[0];
+    let name = |id| {
+        face.names()
+            .into_iter()
+            .filter(|name| name.name_id == id)
+            .find_map(|name| name.to_string())
+            .unwrap_or_default()
+    };
+    println!("family\t{}", name(ttf::name_id::FAMILY));
+    println!("subfamily\t{}", name(ttf::name_id::SUBFAMILY));
+    println!("units per em\t{}", face.units_per_em());
+    println!("ascender\t{}", face.ascender());
+    println!("descender\t{}", face.descender());
+    println!("glyphs\t{}", face.number_of_glyphs());
+    let tables = face.tables();
+    let colr = face.table_data(ttf::Tag::from_bytes(b"COLR")).is_some();
+    for (table, present) in [
+        ("kern", tables.kern.is_some()),
+        ("GPOS", tables.gpos.is_some()),
+        ("GSUB", tables.gsub.is_some()),
+        ("COLR", colr),
+    ] {
+        println!("{}\t{}", table, if present { "yes" } else { "no" });
+    }
+    let axes = face
+        .variation_axes()
+        .into_iter()
+        .map(|axis| {
+            format!(
+                "{} {} to {}, {} by default",
+                axis.tag, axis.min_value, axis.max_value, axis.def_value
+            )
+        })
+        .collect::<Vec<_>>();
+    if axes.is_empty() {
+        println!("axes\tnone");
+    }
+    for axis in axes {
+        println!("axis\t{}", axis);
+    }
+
+    let mut used = HashSet::new();
+    let mut missing = vec![];
+    for line in lines {
+        for c in drawn_chars(faces, &line.text, options) {
+            if used.insert(c) && lookup(faces, c).is_none() {
+                missing.push(c);
+            }
+        }
+    }
+    missing.sort_unstable();
+    println!("characters\t{}", used.len());
+    println!("missing\t{}", missing.len());
+    for c in &missing {
+        println!("\t{}", describe_chars(&[*c]));
+    }
+    missing.is_empty()
+}
+
+/// Prints each variation axis as its tag, minimum, default and maximum.
+fn list_variations(face: &ttf::Face) {
+    for axis in face.variation_axes() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            axis.tag, axis.min_value, axis.def_value, axis.max_value
+        );
+    }
+}
+
+/// What --dry-run and the check before a run need to name the files.
+struct Plan<'a> {
+    files: &'a [String],
+    per_file: bool,
+    start_index: usize,
+    single_file: bool,
+    strict: bool,
+    allow_collisions: bool,
+    continue_on_error: bool,
+}
+
+/// The one based line a file is for, `None` for the single file of all of
+/// them, and where it goes.
+type Target = (Option<usize>, PathBuf);
+
+/// The characters --glyph-per-file writes a file for.
+fn outlined(
+    faces: &[ttf::Face],
+    text: &str,
+    options: &RenderOptions,
+) -> Vec<char> {
+    cased(text, options)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .filter(|&c| {
+            lookup(faces, c)
+                .or_else(|| lookup(faces, options.placeholder?))
+                .is_some()
+        })
+        .collect()
+}
+
+impl Plan<'_> {
+    /// Where every file would be written, in the order a run writes them,
+    /// without touching the output. Fails on the first name that would
+    /// leave the output directory.
+    fn targets(
+        &self,
+        faces: &[ttf::Face],
+        lines: &[InputLine],
+        output: &Output,
+        options: &RenderOptions,
+    ) -> Result<Vec<Target>, Error> {
+        let mut output = output.planner();
+        let mut targets = vec![];
+        let mut glyph_count = 0;
+        for line in lines {
+            let file = &self.files[line.file];
+            let number = Some(line.index + 1);
+            if options.glyph_per_file {
+                for c in outlined(faces, &line.text, options) {
+                    let label = (glyph_count + self.start_index).to_string();
+                    let text = c.to_string();
+                    let path =
+                        output.plan(&label, file, &text, &line.columns)?;
+                    targets.push((number, path));
+                    glyph_count += 1;
+                }
+            } else if !self.single_file {
+                let index = if self.per_file {
+                    line.file_index
+                } else {
+                    line.index
+                };
+                let label = (index + self.start_index).to_string();
+                let path =
+                    output.plan(&label, file, &line.text, &line.columns)?;
+                targets.push((number, path));
+            }
+        }
+        if self.single_file {
+            targets.push((None, output.plan("", "", "", &[])?));
+        }
+        Ok(targets)
+    }
+
+    /// Fails on the first file more than one line would be written to,
+    /// unless --allow-collisions lets them share it.
+    fn check_collisions(&self, targets: &[Target]) -> Result<(), Error> {
+        if self.allow_collisions {
+            return Ok(());
+        }
+        let mut lines: HashMap<&Path, Vec<usize>> = HashMap::new();
+        for (number, path) in targets {
+            lines.entry(path).or_default().extend(number);
+        }
+        for (_, path) in targets {
+            let mut numbers = lines[path.as_path()].clone();
+            if numbers.len() > 1 {
+                numbers.dedup();
+                return Err(Error::Collision {
+                    path: path.clone(),
+                    lines: numbers,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the file each line would be written to and what it all comes
+    /// to, with the warnings and errors a real run would give, the input's
+    /// already printed. Fails with the status the run would exit with.
+    fn print(
+        &self,
+        faces: &[ttf::Face],
+        lines: &[InputLine],
+        output: &Output,
+        options: &RenderOptions,
+        input_errors: &[Error],
+    ) -> Result<(), i32> {
+        let (mut glyph_count, mut missing_count) = (0, 0);
+        for line in lines {
+            let missing = missing_chars(faces, &line.text, options);
+            if !missing.is_empty() {
+                eprintln!(
+                    "{}line {} has no glyph for {}",
+                    YELLOW_WARNING,
+                    line.index + 1,
+                    describe_chars(&missing)
+                );
+                missing_count += missing.len();
+            }
+            glyph_count += if options.glyph_per_file {
+                outlined(faces, &line.text, options).len()
+            } else {
+                glyphs(faces, &line.text, options).len()
+            };
+        }
+        let targets = match self.targets(faces, lines, output, options) {
+            Ok(targets) => targets,
+            Err(e) => {
+                eprintln!("{}{}", RED_ERROR, e);
+                return Err(e.exit_code());
+            }
+        };
+        // A real run checks the names before anything else, and stops at
+        // the first failed file unless it's to carry on.
+        let mut checked = Ok(());
+        if output.names_files() {
+            if let Err(e) = self.check_collisions(&targets) {
+                eprintln!("{}{}", RED_ERROR, e);
+                checked = Err(e.exit_code());
+            }
+        }
+        let mut failures: Vec<i32> =
+            input_errors.iter().map(Error::exit_code).collect();
+        let mut files = 0;
+        for (number, path) in &targets {
+            if output.refuses(path) {
+                let e = Error::Exists { path: path.clone() };
+                eprintln!("{}{}", RED_ERROR, e);
+                failures.push(e.exit_code());
+                continue;
+            }
+            match number {
+                Some(number) => println!("{}\t{}", number, path.display()),
+                None => println!("all\t{}", path.display()),
+            }
+            files += 1;
+        }
+        println!(
+            "{} line(s) -> {} file(s) in {}, about {} glyph(s), {} missing",
+            lines.len(),
+            files,
+            output.destination(),
+            glyph_count,
+            missing_count
+        );
+        checked?;
+        match failures.first() {
+            Some(_) if self.continue_on_error => Err(1),
+            Some(&code) => Err(code),
+            None if self.strict && missing_count > 0 => Err(1),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Prints each line that has characters no font can map, and which.
+fn report_missing(
+    faces: &[ttf::Face],
+    lines: &[InputLine],
+    options: &RenderOptions,
+) {
+    for line in lines {
+        let missing = missing_chars(faces, &line.text, options);
+        if !missing.is_empty() {
+            let number = line.index + 1;
+            println!("line {}: {}", number, describe_chars(&missing));
+        }
+    }
+}
+
+/// Renders every line without writing anything and reports the throughput.
+fn run_benchmark(
+    faces: &[ttf::Face],
+    lines: &[InputLine],
+    options: &RenderOptions,
+) {
+    let start = Instant::now();
+    let mut glyph_count = 0;
+    for line in lines {
+        let glyphs = glyphs(faces, &line.text, options);
+        glyph_count += glyphs.len();
+        render(&faces[0], &glyphs, options, "benchmark", &line.text);
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "Rendered {} lines ({} glyphs) in {:.3}s: {:.1} lines/sec, \
+         {:.1} glyphs/sec",
+        lines.len(),
+        glyph_count,
+        seconds,
+        lines.len() as f64 / seconds,
+        glyph_count as f64 / seconds
+    );
+}
+
+fn main() {
+    let Args {
+        faces,
+        lines,
+        files,
+        titles,
+        per_file,
+        input_errors,
+        mut output,
+        mut options,
+        continue_on_error,
+        glyph_index_map,
+        advance_width_table,
+        metrics,
+        benchmark,
+        measure_missing,
+        inspect,
+        template,
+        preview,
+        bbox_union_report,
+        strict,
+        glyph_padding_cells,
+        single_file,
+        start_index,
+        glyph_render_stats,
+        glyph_cache_stats,
+        jobs,
+        progress,
+        format,
+        dry_run,
+        allow_collisions,
+    } = match parse_arguments() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}{}", RED_ERROR, e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    if benchmark {
+        run_benchmark(&faces, &lines, &options);
+        return;
+    }
+    if measure_missing {
+        report_missing(&faces, &lines, &options);
+        return;
+    }
+    if inspect {
+        for e in &input_errors {
+            eprintln!("{}{}", RED_ERROR, e);
+        }
+        let covered = report_inspection(&faces, &lines, &options);
+        if !covered || !input_errors.is_empty() {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let plan = Plan {
+        files: &files,
+        per_file,
+        start_index,
+        single_file,
+        strict,
+        allow_collisions,
+        continue_on_error,
+    };
+    if dry_run {
+        for e in &input_errors {
+            eprintln!("{}{}", RED_ERROR, e);
+        }
+        let planned =
+            plan.print(&faces, &lines, &output, &options, &input_errors);
+        if let Err(code) = planned {
+            std::process::exit(code);
+        }
+        return;
+    }
+    // Every name is worked out before anything is rendered, so two lines
+    // sharing a file or a name leaving the directory stop the run before
+    // the first file rather than partway.
+    if output.names_files() {
+        let checked = plan
+            .targets(&faces, &lines, &output, &options)
+            .and_then(|targets| plan.check_collisions(&targets));
+        if let Err(e) = checked {
+            eprintln!("{}{}", RED_ERROR, e);
+            if let Output::Directory(directory) = &mut output {
+                directory.discard();
+            }
+            std::process::exit(e.exit_code());
+        }
+    }
+
+    // What's left behind by a run that stops partway, removed if it does.
+    let staging = match &output {
+        Output::Directory(directory) => {
+            directory.staging().map(Path::to_path_buf)
+        }
+        Output::Zip(archive) => Some(archive.partial().to_path_buf()),
+        _ => None,
+    };
+    let mut failures = 0;
+    // Every file that was meant to be written, whether or not it was.
+    let mut attempts = 0;
+    let mut report = |result: Result<(), Error>| {
+        attempts += 1;
+        if let Err(e) = result {
+            eprintln!("{}{}", RED_ERROR, e);
+            if !continue_on_error {
+                if let Some(staging) = &staging {
+                    let _ = if staging.is_dir() {
+                        std::fs::remove_dir_all(staging)
+                    } else {
+                        std::fs::remove_file(staging)
+                    };
+                }
+                std::process::exit(e.exit_code());
+            }
+            failures += 1;
+        }
+    };
+    for e in input_errors {
+        report(Err(e));
+    }
+
+    if glyph_padding_cells {
+        // Every glyph that'll get a file, the biggest decides the cell.
+        let mut cell = (0.0f32, 0.0f32);
+        for line in &lines {
+            let text = cased(&line.text, &options);
+            for c in text.chars().filter(|c| !c.is_whitespace()) {
+                if let Some(glyph) = lookup(&faces, c) {
+                    let size = canvas_size(&faces[0], &[glyph], &options);
+                    cell = (cell.0.max(size.0), cell.1.max(size.1));
+                }
+            }
+        }
+        options.cell = Some(cell);
+    }
+
+    let finish = |svg: String| match &template {
+        Some(template) => template.apply(&svg),
+        None => svg,
+    };
+    let encode = |svg: String| match format {
+        Format::Svg => Ok(svg.into_bytes()),
+        Format::Png(scale) => raster::png(&svg, scale).map_err(Error::Raster),
+        Format::Pdf => pdf::pdf(&[svg]).map_err(Error::Raster),
+    };
+    // A pdf of one file gets a page per line, rather than one tall page.
+    let paged = single_file && format == Format::Pdf;
+    let mut pages = vec![];
+
+    // Union of every svg's canvas, each stacked below the one before, so
+    // the widest width and the total height.
+    let mut extent = (0.0f32, 0.0f32);
+    let mut missing_count = 0;
+    let mut stats = Stats::default();
+    // Svgs rendered ahead of the line being written.
+    let mut rendered = std::collections::VecDeque::new();
+    let mut glyph_count = 0;
+    let mut glyph_map = String::from("line\tchar\tcodepoint\tglyph\n");
+    let mut document = vec![];
+    // What each line of the document is numbered, for its class.
+    let mut numbers = vec![];
+    let mut entries: Vec<MetricsEntry> = vec![];
+    // Every distinct glyph drawn, with the first character it was drawn for.
+    let mut advanced: Vec<(Glyph, char)> = vec![];
+    // Only for people watching, not logs.
+    let mut progress = if progress && io::stderr().is_terminal() {
+        Some(Progress::new(lines.len()))
+    } else {
+        None
+    };
+    for (
+        position,
+        InputLine {
+            text: line,
+            columns,
+            repeats,
+            index: number,
+            file,
+            file_index,
+            ..
+        },
+    ) in lines.iter().enumerate()
+    {
+        if let Some(progress) = &mut progress {
+            progress.update(position);
+        }
+        // What glyphs are looked up for, where that's done a character at a
+        // time.
+        let chars = cased(line, &options);
+        if glyph_index_map.is_some() {
+            for c in chars.chars() {
+                let glyph = lookup(&faces, c);
+                writeln!(
+                    &mut glyph_map,
+                    "{}\t{}\tU+{:04X}\t{}",
+                    *number + 1,
+                    c.escape_debug(),
+                    c as u32,
+                    glyph
+                        .map(|glyph| glyph.id.0.to_string())
+                        .unwrap_or_default()
+                )
+                .unwrap();
+            }
+        }
+        if advance_width_table.is_some() {
+            for c in chars.chars() {
+                if let Some(glyph) = lookup(&faces, c) {
+                    if !advanced.iter().any(|(seen, _)| {
+                        std::ptr::eq(seen.face, glyph.face)
+                            && seen.id == glyph.id
+                    }) {
+                        advanced.push((glyph, c));
+                    }
+                }
+            }
+        }
+        let missing = missing_chars(&faces, line, &options);
+        if !missing.is_empty() {
+            // stderr, so it never ends up mixed into svgs on stdout.
+            eprintln!(
+                "{}line {} has no glyph for {}",
+                YELLOW_WARNING,
+                *number + 1,
+                describe_chars(&missing)
+            );
+            missing_count += missing.len();
+        }
+        if options.glyph_per_file {
+            // Each outlined character gets its own file, numbered across
+            // the whole input rather than per line.
+            for c in chars.chars().filter(|c| !c.is_whitespace()) {
+                let glyph = match lookup(&faces, c)
+                    .or_else(|| lookup(&faces, options.placeholder?))
+                {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                let id = format!("glyph-{}", glyph_count);
+                let text = c.to_string();
+                let (svg, size) =
+                    render(&faces[0], &[glyph], &options, &id, &text);
+                if size.0 <= 0.0 {
+                    report(Err(Error::Glyph {
+                        character: c,
+                        number: *number + 1,
+                        line: line.clone(),
+                        source: Box::new(Error::NoWidth(size.0)),
+                    }));
+                    glyph_count += 1;
+                    continue;
+                }
+                extent = (extent.0.max(size.0), extent.1 + size.1);
+                let label = (glyph_count + start_index).to_string();
+                let text = c.to_string();
+                let data = encode(finish(svg));
+                let bytes = data.as_ref().map_or(0, Vec::len);
+                let result = data.and_then(|data| {
+                    output.write_file(
+                        &label,
+                        &files[*file],
+                        &text,
+                        columns,
+                        data,
+                    )
+                });
+                if result.is_ok() {
+                    stats.add(&[glyph], bytes);
+                }
+                report(result.map(|_| ()).map_err(|e| Error::Glyph {
+                    character: c,
+                    number: *number + 1,
+                    line: line.clone(),
+                    source: Box::new(e),
+                }));
+                glyph_count += 1;
+            }
+            stats.lines += 1;
+            continue;
+        }
+        if single_file && !paged {
+            let glyphs = glyphs(&faces, line, &options);
+            if metrics.is_some() {
+                entries.push(MetricsEntry {
+                    file: None,
+                    text: line.clone(),
+                    metrics: line_metrics(&faces[0], &glyphs, &options),
+                });
+            }
+            document.push(glyphs);
+            numbers.push(*number + start_index);
+            continue;
+        }
+        if rendered.is_empty() {
+            // Enough lines at a time to keep every thread busy without
+            // holding the whole input's svgs in memory.
+            let end = lines.len().min(position + jobs * 64);
+            rendered.extend(render_lines(
+                &faces,
+                &lines[position..end],
+                &titles,
+                &options,
+                jobs,
+            ));
+        }
+        let (glyphs, svg, size) = rendered.pop_front().unwrap();
+        let options = line_options(&options, &lines[position]);
+        let mut entry = metrics.as_ref().map(|_| MetricsEntry {
+            file: None,
+            text: line.clone(),
+            metrics: line_metrics(&faces[0], &glyphs, &options),
+        });
+        if size.0 <= 0.0 && !glyphs.is_empty() {
+            entries.extend(entry);
+            report(Err(Error::Line {
+                number: *number + 1,
+                line: line.clone(),
+                source: Box::new(Error::NoWidth(size.0)),
+            }));
+            continue;
+        }
+        extent = (extent.0.max(size.0), extent.1 + size.1);
+        let svg = if *repeats > 1 {
+            annotate_repeats(svg, *repeats, size, &options)
+        } else {
+            svg
+        };
+        if paged {
+            entries.extend(entry);
+            pages.push(finish(svg));
+            document.push(glyphs);
+            continue;
+        }
+        // Files are numbered by line, so repeated lines don't overwrite
+        // each other.
+        let number = if per_file { file_index } else { number };
+        let label = (*number + start_index).to_string();
+        let data = encode(finish(svg));
+        let bytes = data.as_ref().map_or(0, Vec::len);
+        let result = data.and_then(|data| {
+            output.write_file(&label, &files[*file], line, columns, data)
+        });
+        if let Some(mut entry) = entry.take() {
+            entry.file = result.as_ref().ok().cloned();
+            entries.push(entry);
+        }
+        if result.is_ok() {
+            stats.lines += 1;
+            stats.add(&glyphs, bytes);
+        }
+        report(result.map(|_| ()).map_err(|e| Error::Line {
+            number: *number + 1,
+            line: line.clone(),
+            source: Box::new(e),
+        }));
+    }
+
+    if single_file {
+        let data = if paged {
+            pdf::pdf(&pages).map_err(Error::Raster)
+        } else {
+            let text = lines
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let (svg, size) = render_document(
+                &faces[0], &document, &numbers, &options, &text,
+            );
+            extent = size;
+            encode(finish(svg))
+        };
+        let bytes = data.as_ref().map_or(0, Vec::len);
+        let result =
+            data.and_then(|data| output.write_file("", "", "", &[], data));
+        for entry in &mut entries {
+            entry.file = result.as_ref().ok().cloned();
+        }
+        if result.is_ok() {
+            for glyphs in &document {
+                stats.add(glyphs, 0);
+            }
+            stats.lines += document.len();
+            stats.bytes += bytes;
+        }
+        report(result.map(|_| ()));
+    }
+
+    if let Some(path) = advance_width_table {
+        let mut table = String::from("glyph\tchar\tcodepoint\tadvance\n");
+        for (glyph, c) in advanced {
+            writeln!(
+                &mut table,
+                "{}\t{}\tU+{:04X}\t{}",
+                glyph.id.0,
+                c.escape_debug(),
+                c as u32,
+                advance_width(&faces[0], glyph, &options)
+            )
+            .unwrap();
+        }
+        report(
+            std::fs::write(&path, &table)
+                .map_err(|source| Error::Write { path, source }),
+        );
+    }
+
+    if let Some(path) = metrics {
+        report(
+            std::fs::write(&path, metrics_json(&entries))
+                .map_err(|source| Error::Write { path, source }),
+        );
+    }
+
+    if let Some(path) = glyph_index_map {
+        report(
+            std::fs::write(&path, &glyph_map)
+                .map_err(|source| Error::Write { path, source }),
+        );
+    }
+
+    // Sidecar files aren't staged, a failure writing one still keeps the
+    // svgs from being moved in.
+    if let Output::Directory(directory) = &mut output {
+        if failures > 0 {
+            directory.discard();
+        } else if let Err(e) = directory.commit() {
+            eprintln!("{}{}", RED_ERROR, e);
+            std::process::exit(e.exit_code());
+        }
+    }
+    // Lines that failed are missing from the archive, but the rest is kept.
+    if let Output::Zip(archive) = &mut output {
+        if let Err(e) = archive.finish() {
+            eprintln!("{}{}", RED_ERROR, e);
+            std::process::exit(e.exit_code());
+        }
+    }
+
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+    if glyph_render_stats || progress.is_some() {
+        // stderr, like the warnings, to stay out of svgs on stdout.
+        eprintln!(
+            "{} line(s), {} glyph(s) ({} unique), {} missing, {} byte(s) \
+             written",
+            stats.lines,
+            stats.glyphs,
+            stats.unique.len(),
+            missing_count,
+            stats.bytes
+        );
+    }
+    if glyph_cache_stats {
+        let CacheStats {
+            hits,
+            misses,
+            entries,
+        } = options.outlines.stats();
+        let lookups = (hits + misses).max(1);
+        eprintln!(
+            "glyph cache: {} hit(s), {} miss(es), {}% hits, {} glyph(s) \
+             cached",
+            hits,
+            misses,
+            hits * 100 / lookups,
+            entries
+        );
+    }
+
+    if bbox_union_report {
+        eprintln!("Union bounding box: 0 0 {} {}", extent.0, extent.1);
+    }
+
+    if preview {
+        if let Some(path) = output.first_file() {
+            open_preview(path);
+        }
+    }
+
+    if missing_count > 0 {
+        eprintln!(
+            "{}{} character(s) had no glyph in any font.",
+            YELLOW_WARNING, missing_count
+        );
+    }
+
+    if failures > 0 {
+        eprintln!("{}{} of {} files failed.", RED_ERROR, failures, attempts);
+        std::process::exit(1);
+    }
+    if strict && missing_count > 0 {
+        std::process::exit(1);
     }
 }
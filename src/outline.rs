@@ -0,0 +1,323 @@
+#[derive(Clone, Copy)]
+pub enum Segment {
+    Line(f32, f32),
+    Quad(f32, f32, f32, f32),
+    Curve(f32, f32, f32, f32, f32, f32),
+}
+
+impl Segment {
+    fn end(&self) -> (f32, f32) {
+        match *self {
+            Segment::Line(x, y)
+            | Segment::Quad(_, _, x, y)
+            | Segment::Curve(_, _, _, _, x, y) => (x, y),
+        }
+    }
+}
+
+pub struct Contour {
+    start: (f32, f32),
+    segments: Vec<Segment>,
+}
+
+impl Contour {
+    /// Every point of the contour in order, control points included.
+    fn points(&self) -> Vec<(f32, f32)> {
+        let mut points = vec![self.start];
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(x, y) => points.push((x, y)),
+                Segment::Quad(x1, y1, x, y) => {
+                    points.extend_from_slice(&[(x1, y1), (x, y)])
+                }
+                Segment::Curve(x1, y1, x2, y2, x, y) => {
+                    points.extend_from_slice(&[(x1, y1), (x2, y2), (x, y)])
+                }
+            }
+        }
+        points
+    }
+
+    fn set_points(&mut self, points: &[(f32, f32)]) {
+        let mut points = points.iter().copied();
+        let mut next = || points.next().unwrap();
+        self.start = next();
+        for segment in &mut self.segments {
+            *segment = match *segment {
+                Segment::Line(..) => {
+                    let (x, y) = next();
+                    Segment::Line(x, y)
+                }
+                Segment::Quad(..) => {
+                    let ((x1, y1), (x, y)) = (next(), next());
+                    Segment::Quad(x1, y1, x, y)
+                }
+                Segment::Curve(..) => {
+                    let ((x1, y1), (x2, y2), (x, y)) = (next(), next(), next());
+                    Segment::Curve(x1, y1, x2, y2, x, y)
+                }
+            }
+        }
+    }
+
+    /// Runs the contour the other way round without changing its shape.
+    fn reverse(&mut self) {
+        let mut starts = vec![self.start];
+        starts.extend(self.segments.iter().map(Segment::end));
+        self.start = *starts.last().unwrap();
+        self.segments = self
+            .segments
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, segment)| {
+                let (x, y) = starts[i];
+                match *segment {
+                    Segment::Line(..) => Segment::Line(x, y),
+                    Segment::Quad(x1, y1, ..) => Segment::Quad(x1, y1, x, y),
+                    Segment::Curve(x1, y1, x2, y2, ..) => {
+                        Segment::Curve(x2, y2, x1, y1, x, y)
+                    }
+                }
+            })
+            .collect();
+    }
+
+    /// Even-odd test of a point against the control polygon.
+    fn contains(&self, (x, y): (f32, f32)) -> bool {
+        let points = self.points();
+        let mut inside = false;
+        for (i, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0)
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Shoelace area over the control polygon, positive when the contour
+    /// runs counter-clockwise in font space.
+    fn signed_area(&self) -> f32 {
+        let points = self.points();
+        let mut area = 0.0;
+        for (i, (x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(i + 1) % points.len()];
+            area += x0 * y1 - x1 * y0;
+        }
+        area / 2.0
+    }
+}
+
+/// A glyph outline recorded in font units, so it can be adjusted before
+/// being written out through a `Builder`.
+#[derive(Default)]
+pub struct Outline {
+    pub contours: Vec<Contour>,
+}
+
+impl ttf_parser::OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.contours.push(Contour {
+            start: (x, y),
+            segments: vec![],
+        });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.segments.push(Segment::Line(x, y));
+        }
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.segments.push(Segment::Quad(x1, y1, x, y));
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.segments.push(Segment::Curve(x1, y1, x2, y2, x, y));
+        }
+    }
+
+    fn close(&mut self) {}
+}
+
+impl Outline {
+    /// `(x_min, y_min, x_max, y_max)` over every point, control points
+    /// included like a glyf bounding box. `None` without any contours.
+    pub fn bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        self.contours
+            .iter()
+            .flat_map(Contour::points)
+            .map(|(x, y)| (x, y, x, y))
+            .reduce(|a, b| {
+                (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+            })
+    }
+
+    pub fn replay(&self, builder: &mut dyn ttf_parser::OutlineBuilder) {
+        for contour in &self.contours {
+            builder.move_to(contour.start.0, contour.start.1);
+            for segment in &contour.segments {
+                match *segment {
+                    Segment::Line(x, y) => builder.line_to(x, y),
+                    Segment::Quad(x1, y1, x, y) => {
+                        builder.quad_to(x1, y1, x, y)
+                    }
+                    Segment::Curve(x1, y1, x2, y2, x, y) => {
+                        builder.curve_to(x1, y1, x2, y2, x, y)
+                    }
+                }
+            }
+            builder.close();
+        }
+    }
+
+    /// Counters have to wind the opposite way to the contour around them
+    /// or the nonzero fill paints them in. Contours that disagree with
+    /// their nesting depth are reversed, returning whether any were.
+    pub fn fix_winding(&mut self) -> bool {
+        let depths = self
+            .contours
+            .iter()
+            .enumerate()
+            .map(|(i, contour)| {
+                self.contours
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| {
+                        i != j && other.contains(contour.start)
+                    })
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        // The biggest outer contour decides which way is "outside".
+        let outer = match (0..self.contours.len())
+            .filter(|&i| depths[i] == 0)
+            .max_by(|&a, &b| {
+                let area = |i: usize| self.contours[i].signed_area().abs();
+                area(a).partial_cmp(&area(b)).unwrap()
+            }) {
+            Some(outer) => self.contours[outer].signed_area() > 0.0,
+            None => return false,
+        };
+        let mut fixed = false;
+        for (contour, depth) in self.contours.iter_mut().zip(depths) {
+            let expected = outer == (depth % 2 == 0);
+            let area = contour.signed_area();
+            if area != 0.0 && (area > 0.0) != expected {
+                contour.reverse();
+                fixed = true;
+            }
+        }
+        fixed
+    }
+
+    /// Drops moves that draw nothing, zero length lines and the final line
+    /// back to the start that `Z` already implies, and merges runs of
+    /// collinear lines into one.
+    pub fn optimize(&mut self) {
+        self.contours.retain(|contour| !contour.segments.is_empty());
+        for contour in &mut self.contours {
+            let mut segments: Vec<Segment> = vec![];
+            let mut current = contour.start;
+            // Where the last kept line started, if it was a line.
+            let mut line_start: Option<(f32, f32)> = None;
+            for segment in contour.segments.drain(..) {
+                let end = segment.end();
+                if let Segment::Line(x, y) = segment {
+                    if end == current {
+                        continue;
+                    }
+                    if let Some(start) = line_start {
+                        let (ax, ay) =
+                            (current.0 - start.0, current.1 - start.1);
+                        let (bx, by) = (x - current.0, y - current.1);
+                        let cross = ax * by - ay * bx;
+                        let dot = ax * bx + ay * by;
+                        let lengths = ax.hypot(ay) * bx.hypot(by);
+                        if cross.abs() <= 1e-6 * lengths && dot > 0.0 {
+                            *segments.last_mut().unwrap() = segment;
+                            current = end;
+                            continue;
+                        }
+                    }
+                    line_start = Some(current);
+                } else {
+                    line_start = None;
+                }
+                segments.push(segment);
+                current = end;
+            }
+            if let Some(Segment::Line(x, y)) = segments.last() {
+                if (*x, *y) == contour.start {
+                    segments.pop();
+                }
+            }
+            contour.segments = segments;
+        }
+    }
+
+    /// Pushes every point outwards along the bisector of its neighbouring
+    /// edges, growing the outline by `amount` overall. This is the same
+    /// approach FreeType takes for synthetic bold.
+    pub fn embolden(&mut self, amount: f32) {
+        let strength = amount / 2.0;
+        let area: f32 = self.contours.iter().map(Contour::signed_area).sum();
+        // TrueType outlines run clockwise, PostScript ones the other way.
+        let clockwise = area < 0.0;
+        for contour in &mut self.contours {
+            let points = contour.points();
+            let len = points.len();
+            let distinct = |from: usize, step: usize| {
+                let mut i = from;
+                for _ in 0..len {
+                    i = (i + step) % len;
+                    if points[i] != points[from] {
+                        return Some(points[i]);
+                    }
+                }
+                None
+            };
+            let shifted = points
+                .iter()
+                .enumerate()
+                .map(|(i, &(x, y))| {
+                    let (prev, next) =
+                        match (distinct(i, len - 1), distinct(i, 1)) {
+                            (Some(prev), Some(next)) => (prev, next),
+                            _ => return (x, y),
+                        };
+                    let normalize = |(dx, dy): (f32, f32)| {
+                        let length = (dx * dx + dy * dy).sqrt();
+                        (dx / length, dy / length)
+                    };
+                    let incoming = normalize((x - prev.0, y - prev.1));
+                    let outgoing = normalize((next.0 - x, next.1 - y));
+                    let d = incoming.0 * outgoing.0 + incoming.1 * outgoing.1;
+                    // Near hairpin turns the bisector blows up, leave those.
+                    if d <= -0.9375 {
+                        return (x, y);
+                    }
+                    let d = d + 1.0;
+                    let mut shift =
+                        (incoming.1 + outgoing.1, -(incoming.0 + outgoing.0));
+                    let mut q =
+                        outgoing.0 * incoming.1 - outgoing.1 * incoming.0;
+                    if clockwise {
+                        shift = (-shift.0, -shift.1);
+                        q = -q;
+                    }
+                    let factor =
+                        if q <= d { strength / d } else { strength / q };
+                    (x + shift.0 * factor, y + shift.1 * factor)
+                })
+                .collect::<Vec<_>>();
+            contour.set_points(&shifted);
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::Error,
+    format::FormatString,
+    raster::{crc32, deflate},
+};
+
+pub enum Output {
+    Directory(Directory),
+    /// One base64 encoded file per line on stdout.
+    Base64Stdout,
+    /// The files themselves on stdout, with the separator between them.
+    Stdout {
+        separator: String,
+        written: usize,
+    },
+    /// Everything in the one file.
+    File(PathBuf, Overwrite),
+    Zip(Archive),
+}
+
+/// What happens to files that are already there.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Overwrite {
+    /// Stop with an error.
+    Refuse,
+    Force,
+    /// Leave them be and carry on with the next one.
+    Skip,
+}
+
+/// Creates the file at `path`, or nothing when told to skip files that
+/// exist. `target` is where it ends up, when it is written somewhere else
+/// first.
+fn create(
+    path: &Path,
+    target: &Path,
+    overwrite: Overwrite,
+) -> Result<Option<File>, Error> {
+    if overwrite != Overwrite::Force && target.exists() {
+        return match overwrite {
+            Overwrite::Skip => Ok(None),
+            _ => Err(Error::Exists {
+                path: target.to_path_buf(),
+            }),
+        };
+    }
+    File::create(path)
+        .map(Some)
+        .map_err(|source| Error::Create {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// Writes the whole file, `path` only naming the destination in errors.
+fn write_data(
+    out: &mut dyn Write,
+    data: &[u8],
+    path: &Path,
+) -> Result<(), Error> {
+    out.write_all(data).map_err(|source| Error::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+impl Output {
+    /// The first file written, if any were.
+    pub fn first_file(&self) -> Option<&Path> {
+        match self {
+            Output::Directory(directory) => directory.first.as_deref(),
+            Output::File(path, _) => Some(path),
+            Output::Base64Stdout | Output::Stdout { .. } | Output::Zip(_) => {
+                None
+            }
+        }
+    }
+
+    /// Where the files go, as --dry-run says it.
+    pub fn destination(&self) -> String {
+        match self {
+            Output::Directory(directory) => {
+                format!("directory {}", directory.directory.display())
+            }
+            Output::Zip(archive) => {
+                format!("archive {}", archive.path.display())
+            }
+            Output::File(path, _) => format!("file {}", path.display()),
+            Output::Base64Stdout => "stdout as base64".to_string(),
+            Output::Stdout { .. } => "stdout".to_string(),
+        }
+    }
+
+    /// An output that names files the way this one would from here on,
+    /// for planning a run without moving on where the next file goes.
+    pub fn planner(&self) -> Output {
+        match self {
+            Output::Directory(directory) => Output::Directory(Directory {
+                format: directory.format.clone(),
+                directory: directory.directory.clone(),
+                shard_size: directory.shard_size,
+                written: directory.written,
+                first: None,
+                staging: None,
+                names: directory.names.clone(),
+                overwrite: directory.overwrite,
+            }),
+            Output::Zip(archive) => Output::Zip(Archive {
+                format: archive.format.clone(),
+                path: archive.path.clone(),
+                partial: archive.partial.clone(),
+                file: None,
+                shard_size: archive.shard_size,
+                compression: archive.compression,
+                offset: 0,
+                named: archive.named,
+                entries: vec![],
+                names: archive.names.clone(),
+            }),
+            Output::Base64Stdout => Output::Base64Stdout,
+            Output::Stdout { separator, written } => Output::Stdout {
+                separator: separator.clone(),
+                written: *written,
+            },
+            Output::File(path, overwrite) => {
+                Output::File(path.clone(), *overwrite)
+            }
+        }
+    }
+
+    /// Whether each file gets a name of its own, rather than all going to
+    /// stdout or the one file.
+    pub fn names_files(&self) -> bool {
+        matches!(self, Output::Directory(_) | Output::Zip(_))
+    }
+
+    /// Where `write_file` would put the file, without writing anything.
+    /// Fails on a name that would leave the output directory.
+    pub fn plan(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+    ) -> Result<PathBuf, Error> {
+        match self {
+            Output::Directory(directory) => {
+                let (_, target) =
+                    directory.place(label, file, text, columns)?;
+                Ok(target)
+            }
+            Output::Zip(archive) => {
+                let name = archive.name(label, file, text, columns)?;
+                Ok(archive.path.join(name))
+            }
+            Output::File(path, _) => Ok(path.clone()),
+            Output::Base64Stdout | Output::Stdout { .. } => {
+                Ok(PathBuf::from("-"))
+            }
+        }
+    }
+
+    /// Whether writing to the target would stop at a file already there.
+    pub fn refuses(&self, target: &Path) -> bool {
+        let overwrite = match self {
+            Output::Directory(directory) => directory.overwrite,
+            Output::File(_, overwrite) => *overwrite,
+            // Nothing inside an archive is there yet.
+            _ => return false,
+        };
+        overwrite == Overwrite::Refuse && target.exists()
+    }
+
+    /// Returns where the svg or png went, `-` for stdout.
+    pub fn write_file(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+        data: Vec<u8>,
+    ) -> Result<PathBuf, Error> {
+        let stdout = PathBuf::from("-");
+        match self {
+            Output::Directory(directory) => {
+                directory.write_file(label, file, text, columns, data)
+            }
+            Output::Zip(archive) => {
+                archive.write_file(label, file, text, columns, data)
+            }
+            Output::Base64Stdout => {
+                let text = base64::encode(data) + "\n";
+                write_data(&mut io::stdout(), text.as_bytes(), &stdout)?;
+                Ok(stdout)
+            }
+            Output::Stdout { separator, written } => {
+                if *written > 0 {
+                    let separator = separator.as_bytes();
+                    write_data(&mut io::stdout(), separator, &stdout)?;
+                }
+                *written += 1;
+                write_data(&mut io::stdout(), &data, &stdout)?;
+                Ok(stdout)
+            }
+            Output::File(path, overwrite) => {
+                if let Some(mut file) = create(path, path, *overwrite)? {
+                    write_data(&mut file, &data, path)?;
+                }
+                Ok(path.clone())
+            }
+        }
+    }
+}
+
+/// The file name for the line, with later lines of the same name getting
+/// -2, -3 and so on when nothing else tells them apart. Names a column
+/// puts a path separator in are refused, as they'd be written outside the
+/// output directory.
+fn unique_name(
+    format: &FormatString,
+    names: &mut Vec<String>,
+    label: &str,
+    file: &str,
+    text: &str,
+    columns: &[String],
+) -> Result<String, Error> {
+    let mut name = format.label(label, file, text, columns);
+    if name.contains(['/', '\\']) {
+        return Err(Error::InvalidOutput(format!(
+            "{} isn't a file name, it has a path separator in it.",
+            name
+        )));
+    }
+    if format.can_collide() {
+        let extension = format.extension;
+        let stem = name[..name.len() - extension.len() - 1].to_string();
+        let mut count = 1;
+        while names.contains(&name) {
+            count += 1;
+            name = format!("{}-{}.{}", stem, count, extension);
+        }
+        names.push(name.clone());
+    }
+    Ok(name)
+}
+
+pub struct Directory {
+    format: FormatString,
+    directory: PathBuf,
+    shard_size: Option<usize>,
+    written: usize,
+    first: Option<PathBuf>,
+    /// Where files go until `commit`, when the batch is all or nothing.
+    staging: Option<PathBuf>,
+    /// Names given so far, when lines without a number can share one.
+    names: Vec<String>,
+    pub overwrite: Overwrite,
+}
+
+/// Moves everything in `from` into `to`, merging directories that are in
+/// both.
+fn move_into(from: &Path, to: &Path) -> Result<(), Error> {
+    let entries = std::fs::read_dir(from).map_err(|source| Error::Create {
+        path: to.to_path_buf(),
+        source,
+    })?;
+    for entry in entries {
+        let entry = entry.map_err(|source| Error::Create {
+            path: to.to_path_buf(),
+            source,
+        })?;
+        let target = to.join(entry.file_name());
+        if entry.path().is_dir() && target.is_dir() {
+            move_into(&entry.path(), &target)?;
+        } else {
+            std::fs::rename(entry.path(), &target).map_err(|source| {
+                Error::Create {
+                    path: target.clone(),
+                    source,
+                }
+            })?;
+        }
+    }
+    Ok(())
+}
+
+impl Directory {
+    pub fn new(
+        format: FormatString,
+        directory: PathBuf,
+        shard_size: Option<usize>,
+    ) -> Self {
+        Directory {
+            format,
+            directory,
+            shard_size,
+            written: 0,
+            first: None,
+            staging: None,
+            names: vec![],
+            overwrite: Overwrite::Refuse,
+        }
+    }
+
+    /// Writes into a hidden directory inside the output one from now on,
+    /// which `commit` moves the files out of or `discard` throws away.
+    pub fn stage(&mut self) -> Result<(), Error> {
+        let staging = self
+            .directory
+            .join(format!(".partial-{}", std::process::id()));
+        std::fs::create_dir_all(&staging).map_err(|source| Error::Create {
+            path: staging.clone(),
+            source,
+        })?;
+        self.staging = Some(staging);
+        Ok(())
+    }
+
+    pub fn staging(&self) -> Option<&Path> {
+        self.staging.as_deref()
+    }
+
+    /// Moves the staged files into the output directory.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let staging = match self.staging.take() {
+            Some(staging) => staging,
+            None => return Ok(()),
+        };
+        move_into(&staging, &self.directory)?;
+        // Only empty directories are left behind.
+        let _ = std::fs::remove_dir_all(&staging);
+        if let Some(first) = &mut self.first {
+            if let Ok(relative) = first.strip_prefix(&staging) {
+                *first = self.directory.join(relative);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn discard(&mut self) {
+        if let Some(staging) = self.staging.take() {
+            let _ = std::fs::remove_dir_all(staging);
+        }
+        self.first = None;
+    }
+
+    /// Where the next file is written and where it ends up, the same unless
+    /// it's staged.
+    fn place(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+    ) -> Result<(PathBuf, PathBuf), Error> {
+        let mut path = self.staging.as_ref().unwrap_or(&self.directory).clone();
+        if let Some(shard_size) = self.shard_size {
+            path.push(format!("{:03}", self.written / shard_size));
+        }
+        let name = unique_name(
+            &self.format,
+            &mut self.names,
+            label,
+            file,
+            text,
+            columns,
+        )?;
+        self.written += 1;
+        path.push(name);
+        let target = match &self.staging {
+            Some(staging) => {
+                self.directory.join(path.strip_prefix(staging).unwrap())
+            }
+            None => path.clone(),
+        };
+        Ok((path, target))
+    }
+
+    pub fn write_file(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+        data: Vec<u8>,
+    ) -> Result<PathBuf, Error> {
+        let (path, target) = self.place(label, file, text, columns)?;
+        // Made on the first write rather than up front, so --dry-run
+        // leaves nothing behind.
+        if self.written == 1 || self.shard_size.is_some() {
+            let parent = path.parent().unwrap();
+            std::fs::create_dir_all(parent).map_err(|source| {
+                Error::Create {
+                    path: parent.to_path_buf(),
+                    source,
+                }
+            })?;
+        }
+        let mut file = match create(&path, &target, self.overwrite)? {
+            Some(file) => file,
+            None => return Ok(target),
+        };
+        write_data(&mut file, &data, &path)?;
+        self.first.get_or_insert(path);
+
+        Ok(target)
+    }
+}
+
+/// How entries are kept in a zip.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compression {
+    Stored,
+    Deflate,
+}
+
+/// What the central directory needs to know of an entry.
+struct Entry {
+    name: String,
+    crc: u32,
+    method: u16,
+    compressed: u32,
+    size: u32,
+    offset: u64,
+}
+
+/// A zip archive written an entry at a time, so only the names stay in
+/// memory. It is written under a .partial name until `finish`, so a run
+/// that stops partway never leaves an archive that looks whole. Nothing is
+/// created before the first entry.
+pub struct Archive {
+    format: FormatString,
+    path: PathBuf,
+    partial: PathBuf,
+    file: Option<io::BufWriter<File>>,
+    shard_size: Option<usize>,
+    compression: Compression,
+    /// Bytes written so far, where the next entry starts.
+    offset: u64,
+    /// Entries named so far, written or planned.
+    named: usize,
+    entries: Vec<Entry>,
+    names: Vec<String>,
+}
+
+/// Entries are dated 1980-01-01, the earliest a zip can say, so the same
+/// input always gives the same archive.
+const DOS_DATE: u16 = 0x21;
+
+impl Archive {
+    pub fn new(
+        format: FormatString,
+        path: PathBuf,
+        shard_size: Option<usize>,
+        compression: Compression,
+        overwrite: Overwrite,
+    ) -> Result<Self, Error> {
+        if overwrite == Overwrite::Skip {
+            return Err(Error::InvalidOutput(
+                "--skip-existing can't look inside a zip archive.".to_string(),
+            ));
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        if overwrite == Overwrite::Refuse && path.exists() {
+            return Err(Error::Exists { path });
+        }
+        Ok(Archive {
+            format,
+            path,
+            partial,
+            file: None,
+            shard_size,
+            compression,
+            offset: 0,
+            named: 0,
+            entries: vec![],
+            names: vec![],
+        })
+    }
+
+    /// Where the archive is until it's finished.
+    pub fn partial(&self) -> &Path {
+        &self.partial
+    }
+
+    /// Writes to the end of the partial archive, creating it the first time.
+    fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = create(&self.partial, &self.path, Overwrite::Force)?
+                    .unwrap();
+                self.file.insert(io::BufWriter::new(file))
+            }
+        };
+        write_data(file, data, &self.partial)?;
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    /// The next entry's name, in a shard directory if there are any.
+    fn name(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+    ) -> Result<String, Error> {
+        let name = unique_name(
+            &self.format,
+            &mut self.names,
+            label,
+            file,
+            text,
+            columns,
+        )?;
+        self.named += 1;
+        Ok(match self.shard_size {
+            Some(shard_size) => {
+                format!("{:03}/{}", (self.named - 1) / shard_size, name)
+            }
+            None => name,
+        })
+    }
+
+    /// Adds an entry, returning the path of the archive joined with the
+    /// entry's name.
+    pub fn write_file(
+        &mut self,
+        label: &str,
+        file: &str,
+        text: &str,
+        columns: &[String],
+        data: Vec<u8>,
+    ) -> Result<PathBuf, Error> {
+        let name = self.name(label, file, text, columns)?;
+        let too_big =
+            || Error::InvalidOutput(format!("{} is too big for a zip.", name));
+        let size: u32 = std::convert::TryFrom::try_from(data.len())
+            .map_err(|_| too_big())?;
+        let crc = crc32(&data);
+        let (method, data): (u16, _) = match self.compression {
+            Compression::Deflate => {
+                let deflated = deflate(&data);
+                // Deflating can make tiny files bigger.
+                if deflated.len() < data.len() {
+                    (8, deflated)
+                } else {
+                    (0, data)
+                }
+            }
+            Compression::Stored => (0, data),
+        };
+        let compressed = data.len() as u32;
+        let mut header = vec![];
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        // Names are utf8.
+        header.extend_from_slice(&0x0800u16.to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&compressed.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        let offset = self.offset;
+        self.append(&header)?;
+        self.append(&data)?;
+        self.entries.push(Entry {
+            name: name.clone(),
+            crc,
+            method,
+            compressed,
+            size,
+            offset,
+        });
+        Ok(self.path.join(name))
+    }
+
+    /// Writes the central directory and moves the archive to its name,
+    /// with zip64 records once there are too many entries or bytes for the
+    /// plain ones.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        let start = self.offset;
+        let mut out = vec![];
+        for entry in &self.entries {
+            let zip64 = entry.offset >= 0xFFFF_FFFF;
+            out.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            let version: u16 = if zip64 { 45 } else { 20 };
+            out.extend_from_slice(&version.to_le_bytes());
+            out.extend_from_slice(&version.to_le_bytes());
+            out.extend_from_slice(&0x0800u16.to_le_bytes());
+            out.extend_from_slice(&entry.method.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&DOS_DATE.to_le_bytes());
+            out.extend_from_slice(&entry.crc.to_le_bytes());
+            out.extend_from_slice(&entry.compressed.to_le_bytes());
+            out.extend_from_slice(&entry.size.to_le_bytes());
+            out.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            let extra: u16 = if zip64 { 12 } else { 0 };
+            out.extend_from_slice(&extra.to_le_bytes());
+            // Comment length, disk, internal and external attributes.
+            out.extend_from_slice(&[0; 10]);
+            let offset = entry.offset.min(0xFFFF_FFFF) as u32;
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(entry.name.as_bytes());
+            if zip64 {
+                out.extend_from_slice(&1u16.to_le_bytes());
+                out.extend_from_slice(&8u16.to_le_bytes());
+                out.extend_from_slice(&entry.offset.to_le_bytes());
+            }
+        }
+        let size = out.len() as u64;
+        let count = self.entries.len() as u64;
+        if count >= 0xFFFF || start + size >= 0xFFFF_FFFF {
+            let record = start + size;
+            out.extend_from_slice(&0x0606_4b50u32.to_le_bytes());
+            out.extend_from_slice(&44u64.to_le_bytes());
+            out.extend_from_slice(&45u16.to_le_bytes());
+            out.extend_from_slice(&45u16.to_le_bytes());
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&count.to_le_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&start.to_le_bytes());
+            out.extend_from_slice(&0x0706_4b50u32.to_le_bytes());
+            out.extend_from_slice(&0u32.to_le_bytes());
+            out.extend_from_slice(&record.to_le_bytes());
+            out.extend_from_slice(&1u32.to_le_bytes());
+        }
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        let count = count.min(0xFFFF) as u16;
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&(size.min(0xFFFF_FFFF) as u32).to_le_bytes());
+        out.extend_from_slice(&(start.min(0xFFFF_FFFF) as u32).to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        self.append(&out)?;
+        let file = self.file.as_mut().unwrap();
+        file.flush().map_err(|source| Error::Write {
+            path: self.partial.clone(),
+            source,
+        })?;
+        std::fs::rename(&self.partial, &self.path).map_err(|source| {
+            Error::Create {
+                path: self.path.clone(),
+                source,
+            }
+        })
+    }
+}
@@ -0,0 +1,178 @@
+//! Pdfs of the svgs written here, a page each, drawn from the same outlines
+//! through what `raster` reads of the svg.
+
+use std::fmt::Write;
+
+use svgtypes::{Color, Transform};
+
+use crate::raster::{zlib, Document, Segment, Surface};
+
+/// Points per svg unit, taking units as css pixels.
+const POINTS: f64 = 0.75;
+
+/// Pdf numbers can't have exponents, and three places is plenty.
+fn number(n: f64) -> String {
+    // Adding zero turns -0 into 0.
+    ((n * 1000.0).round() / 1000.0 + 0.0).to_string()
+}
+
+/// A page's content stream, with the opacities it sets.
+#[derive(Default)]
+struct Page {
+    content: String,
+    opacities: Vec<f64>,
+}
+
+impl Page {
+    /// Paints the outline with the operator, `setup` coming after the color.
+    fn paint(
+        &mut self,
+        outline: &[Segment],
+        color: Color,
+        (opacity, stroking): (f64, bool),
+        setup: &str,
+        operator: &str,
+    ) {
+        let alpha = color.alpha as f64 / 255.0 * opacity;
+        if alpha <= 0.0 || outline.is_empty() {
+            return;
+        }
+        let c = &mut self.content;
+        c.push_str("q\n");
+        if alpha < 1.0 {
+            let alpha = (alpha * 1000.0).round() / 1000.0;
+            let index = match self.opacities.iter().position(|&a| a == alpha) {
+                Some(index) => index,
+                None => {
+                    self.opacities.push(alpha);
+                    self.opacities.len() - 1
+                }
+            };
+            writeln!(c, "/a{} gs", index).unwrap();
+        }
+        let [r, g, b] = [color.red, color.green, color.blue]
+            .map(|channel| number(channel as f64 / 255.0));
+        let color_operator = if stroking { "RG" } else { "rg" };
+        writeln!(c, "{} {} {} {}", r, g, b, color_operator).unwrap();
+        c.push_str(setup);
+        for &segment in outline {
+            match segment {
+                Segment::Move((x, y)) => {
+                    writeln!(c, "{} {} m", number(x), number(y))
+                }
+                Segment::Line((x, y)) => {
+                    writeln!(c, "{} {} l", number(x), number(y))
+                }
+                Segment::Cubic(c1, c2, end) => writeln!(
+                    c,
+                    "{} {} {} {} {} {} c",
+                    number(c1.0),
+                    number(c1.1),
+                    number(c2.0),
+                    number(c2.1),
+                    number(end.0),
+                    number(end.1)
+                ),
+                Segment::Close => writeln!(c, "h"),
+            }
+            .unwrap();
+        }
+        writeln!(c, "{}\nQ", operator).unwrap();
+    }
+}
+
+impl Surface for Page {
+    fn fill(&mut self, outline: &[Segment], color: Color, opacity: f64) {
+        self.paint(outline, color, (opacity, false), "", "f");
+    }
+
+    fn stroke(
+        &mut self,
+        outline: &[Segment],
+        width: f64,
+        color: Color,
+        opacity: f64,
+    ) {
+        // Round joins, like pngs get.
+        let setup = format!("{} w 1 j\n", number(width));
+        self.paint(outline, color, (opacity, true), &setup, "S");
+    }
+}
+
+/// A pdf with each svg on a page of its own size.
+pub fn pdf(svgs: &[String]) -> Result<Vec<u8>, String> {
+    let mut objects = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..svgs.len())
+                .map(|i| format!("{} 0 R", 3 + i * 2))
+                .collect::<Vec<_>>()
+                .join(" "),
+            svgs.len()
+        )
+        .into_bytes(),
+    ];
+    for (i, svg) in svgs.iter().enumerate() {
+        let document = Document::parse(svg)?;
+        let (width, height) = document.size;
+        let (width, height) = (width * POINTS, height * POINTS);
+        let mut page = Page::default();
+        // Pdf's y goes up from the bottom.
+        let transform = Transform::new(POINTS, 0.0, 0.0, -POINTS, 0.0, height);
+        document.draw(&transform, &mut page);
+        let states = page
+            .opacities
+            .iter()
+            .enumerate()
+            .map(|(i, alpha)| {
+                format!("/a{} << /ca {} /CA {} >>", i, alpha, alpha)
+            })
+            .collect::<String>();
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /ExtGState << {} >> >> /Contents {} 0 R >>",
+                number(width),
+                number(height),
+                states,
+                4 + i * 2
+            )
+            .into_bytes(),
+        );
+        let content = zlib(page.content.as_bytes());
+        let mut stream = format!(
+            "<< /Length {} /Filter /FlateDecode >>\nstream\n",
+            content.len()
+        )
+        .into_bytes();
+        stream.extend_from_slice(&content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    // The comment's high bytes mark the file as binary.
+    let mut out = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    let mut table =
+        format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in offsets {
+        writeln!(table, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    out.extend_from_slice(table.as_bytes());
+    Ok(out)
+}
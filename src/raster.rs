@@ -0,0 +1,1374 @@
+//! Draws the svgs written here into PNG images, as nothing in the tree
+//! rasterizes svg, or onto another `Surface` like pdf pages. It understands
+//! what the renderer writes and what simple templates add: shapes, paths
+//! and `use`s, filled and stroked, in groups with colors, opacities and
+//! transforms. Text, gradients, clip paths and filters are left out, and
+//! strokes always get round joins.
+
+use std::{collections::HashMap, str::FromStr};
+
+use svgtypes::{
+    Color, Length, LengthUnit, PathParser, PathSegment, PointsParser,
+    Transform, ViewBox,
+};
+
+type Point = (f64, f64);
+
+/// An element, `next` being the index of whatever comes after its children.
+pub(crate) struct Node<'a> {
+    pub name: &'a str,
+    attributes: Vec<(String, String)>,
+    next: usize,
+    /// Byte offsets of the element's start, its children and its end.
+    pub span: (usize, usize, usize),
+}
+
+impl Node<'_> {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        // Later ones win, which puts `style` declarations over attributes.
+        self.attributes
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out + rest
+}
+
+/// The attributes of a start tag, with `style` declarations after them.
+fn attributes(mut text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut attributes = vec![];
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Ok(attributes);
+        }
+        let bad = || format!("attributes `{}` are not valid xml", text);
+        let equals = text.find('=').ok_or_else(bad)?;
+        let name = text[..equals].trim();
+        let value = text[equals + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| matches!(c, '\'' | '"'));
+        let quote = quote.ok_or_else(bad)?;
+        let end = value[1..].find(quote).ok_or_else(bad)? + 1;
+        let unescaped = unescape(&value[1..end]);
+        text = &value[end + 1..];
+        if name == "style" {
+            for declaration in unescaped.split(';') {
+                if let Some((property, value)) = declaration.split_once(':') {
+                    attributes.push((
+                        property.trim().to_string(),
+                        value.trim().to_string(),
+                    ));
+                }
+            }
+        } else {
+            attributes.push((name.to_string(), unescaped));
+        }
+    }
+}
+
+/// The elements in document order.
+pub(crate) fn parse(svg: &str) -> Result<Vec<Node<'_>>, String> {
+    let mut nodes: Vec<Node> = vec![];
+    let mut open: Vec<usize> = vec![];
+    let mut rest = svg;
+    while let Some(at) = rest.find('<') {
+        rest = &rest[at..];
+        let skipped = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>")]
+            .iter()
+            .find(|(start, _)| rest.starts_with(start));
+        if let Some((start, end)) = skipped {
+            let after = rest.find(end).ok_or_else(|| {
+                format!("{} is never closed with {}", start, end)
+            })?;
+            rest = &rest[after + end.len()..];
+            continue;
+        }
+        // A `>` can be inside quotes.
+        let mut quote = None;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| match quote {
+                Some(q) if c == q => {
+                    quote = None;
+                    false
+                }
+                Some(_) => false,
+                None if matches!(c, '\'' | '"') => {
+                    quote = Some(c);
+                    false
+                }
+                None => c == '>',
+            })
+            .map(|(end, _)| end)
+            .ok_or("a tag is never closed")?;
+        let tag = &rest[1..end];
+        let start = svg.len() - rest.len();
+        rest = &rest[end + 1..];
+        let after = svg.len() - rest.len();
+        if tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            let index = open
+                .pop()
+                .ok_or_else(|| format!("</{}> closes nothing", name))?;
+            let next = nodes.len();
+            let node = &mut nodes[index];
+            if node.name != name {
+                return Err(format!(
+                    "<{}> is closed by </{}>",
+                    node.name, name
+                ));
+            }
+            node.next = next;
+            node.span.2 = after;
+            continue;
+        }
+        let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        nodes.push(Node {
+            name: &tag[..name_end],
+            attributes: attributes(&tag[name_end..])?,
+            next: nodes.len() + 1,
+            span: (start, after, after),
+        });
+        if !empty {
+            open.push(nodes.len() - 1);
+        }
+    }
+    match open.last() {
+        Some(&index) => Err(format!("<{}> is never closed", nodes[index].name)),
+        None => Ok(nodes),
+    }
+}
+
+/// `t` after `u`, so `u` applies first.
+fn multiply(t: &Transform, u: &Transform) -> Transform {
+    Transform::new(
+        t.a * u.a + t.c * u.b,
+        t.b * u.a + t.d * u.b,
+        t.a * u.c + t.c * u.d,
+        t.b * u.c + t.d * u.d,
+        t.a * u.e + t.c * u.f + t.e,
+        t.b * u.e + t.d * u.f + t.f,
+    )
+}
+
+fn apply(t: &Transform, (x, y): Point) -> Point {
+    (t.a * x + t.c * y + t.e, t.b * x + t.d * y + t.f)
+}
+
+/// How much the transform scales lengths by, on average.
+fn scale_of(t: &Transform) -> f64 {
+    (t.a * t.d - t.b * t.c).abs().sqrt()
+}
+
+fn color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    // svgtypes only knows #rgb and #rrggbb, and colr layers can have alpha.
+    if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 8) {
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::new_rgba(byte(0)?, byte(2)?, byte(4)?, byte(6)?));
+    }
+    Color::from_str(value).ok()
+}
+
+/// The paint a fill or stroke names, `None` when it's inherited instead.
+fn paint(value: &str, current: Color) -> Option<Option<Color>> {
+    match value.trim() {
+        "none" => Some(None),
+        "currentColor" => Some(Some(current)),
+        "inherit" => None,
+        // Gradients and patterns aren't drawn, only their fallback color.
+        value if value.starts_with("url(") => {
+            Some(value.find(')').and_then(|end| color(&value[end + 1..])))
+        }
+        value => color(value).map(Some),
+    }
+}
+
+/// A length in user units, percentages being of `reference`. Missing or
+/// broken lengths are 0.
+pub(crate) fn length(value: Option<&str>, reference: f64) -> f64 {
+    let length = match value.and_then(|value| Length::from_str(value).ok()) {
+        Some(length) => length,
+        None => return 0.0,
+    };
+    length.number
+        * match length.unit {
+            LengthUnit::None | LengthUnit::Px => 1.0,
+            LengthUnit::Em => 16.0,
+            LengthUnit::Ex => 8.0,
+            LengthUnit::In => 96.0,
+            LengthUnit::Cm => 96.0 / 2.54,
+            LengthUnit::Mm => 96.0 / 25.4,
+            LengthUnit::Pt => 4.0 / 3.0,
+            LengthUnit::Pc => 16.0,
+            LengthUnit::Percent => reference / 100.0,
+        }
+}
+
+fn opacity(value: Option<&str>) -> Option<f64> {
+    value?.trim().parse::<f64>().ok().map(|o| o.clamp(0.0, 1.0))
+}
+
+#[derive(Clone)]
+struct Style {
+    transform: Transform,
+    color: Color,
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    stroke_width: f64,
+    fill_opacity: f64,
+    stroke_opacity: f64,
+    /// Group opacity multiplied down, rather than drawn as a layer.
+    opacity: f64,
+}
+
+impl Style {
+    fn new(transform: Transform) -> Self {
+        Style {
+            transform,
+            color: Color::black(),
+            fill: Some(Color::black()),
+            stroke: None,
+            stroke_width: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+        }
+    }
+
+    /// The style of a child node, `None` when it isn't displayed.
+    fn child(&self, node: &Node, diagonal: f64) -> Option<Style> {
+        if node.get("display") == Some("none") {
+            return None;
+        }
+        let mut style = self.clone();
+        if let Some(transform) = node.get("transform") {
+            if let Ok(transform) = Transform::from_str(transform) {
+                style.transform = multiply(&self.transform, &transform);
+            }
+        }
+        if let Some(color) = node.get("color").and_then(color) {
+            style.color = color;
+        }
+        if let Some(fill) = node.get("fill").and_then(|v| paint(v, style.color))
+        {
+            style.fill = fill;
+        }
+        if let Some(stroke) =
+            node.get("stroke").and_then(|v| paint(v, style.color))
+        {
+            style.stroke = stroke;
+        }
+        if let Some(width) = node.get("stroke-width") {
+            style.stroke_width = length(Some(width), diagonal);
+        }
+        if let Some(fill_opacity) = opacity(node.get("fill-opacity")) {
+            style.fill_opacity = fill_opacity;
+        }
+        if let Some(stroke_opacity) = opacity(node.get("stroke-opacity")) {
+            style.stroke_opacity = stroke_opacity;
+        }
+        style.opacity *= opacity(node.get("opacity")).unwrap_or(1.0);
+        Some(style)
+    }
+}
+
+/// A piece of an outline, in the surface's coordinates.
+#[derive(Clone, Copy)]
+pub(crate) enum Segment {
+    Move(Point),
+    Line(Point),
+    Cubic(Point, Point, Point),
+    Close,
+}
+
+/// The part of an elliptical arc between two angles as cubics, at most a
+/// quarter turn each.
+#[allow(clippy::too_many_arguments)]
+fn arc_cubics(
+    outline: &mut Vec<Segment>,
+    transform: &Transform,
+    center: Point,
+    (rx, ry): Point,
+    (sin, cos): Point,
+    theta: f64,
+    delta: f64,
+) {
+    let at = |angle: f64| {
+        let (s, c) = angle.sin_cos();
+        (
+            (
+                center.0 + cos * rx * c - sin * ry * s,
+                center.1 + sin * rx * c + cos * ry * s,
+            ),
+            (-cos * rx * s - sin * ry * c, -sin * rx * s + cos * ry * c),
+        )
+    };
+    let n = (delta.abs() / std::f64::consts::FRAC_PI_2).ceil().max(1.0);
+    let step = delta / n;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    for i in 0..n as usize {
+        let (a, da) = at(theta + step * i as f64);
+        let (b, db) = at(theta + step * (i + 1) as f64);
+        outline.push(Segment::Cubic(
+            apply(transform, (a.0 + k * da.0, a.1 + k * da.1)),
+            apply(transform, (b.0 - k * db.0, b.1 - k * db.1)),
+            apply(transform, b),
+        ));
+    }
+}
+
+/// An elliptical arc, worked out from its ends the way the svg spec's
+/// implementation notes do.
+#[allow(clippy::too_many_arguments)]
+fn arc(
+    outline: &mut Vec<Segment>,
+    transform: &Transform,
+    from: Point,
+    radii: Point,
+    rotation: f64,
+    large: bool,
+    sweep: bool,
+    to: Point,
+) {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if from == to {
+        return;
+    }
+    if rx == 0.0 || ry == 0.0 {
+        outline.push(Segment::Line(apply(transform, to)));
+        return;
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    // Radii too small to reach are scaled up until they do.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator =
+        (rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1).max(0.0);
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut k = (numerator / denominator).sqrt();
+    if large == sweep {
+        k = -k;
+    }
+    let (cx1, cy1) = (k * rx * y1 / ry, -k * ry * x1 / rx);
+    let center = (
+        cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0,
+        sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0,
+    );
+    let angle = |(ux, uy): Point, (vx, vy): Point| {
+        (ux * vy - uy * vx).atan2(ux * vx + uy * vy)
+    };
+    let start = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let theta = angle((1.0, 0.0), start);
+    let mut delta = angle(start, ((-x1 - cx1) / rx, (-y1 - cy1) / ry));
+    if !sweep && delta > 0.0 {
+        delta -= std::f64::consts::TAU;
+    } else if sweep && delta < 0.0 {
+        delta += std::f64::consts::TAU;
+    }
+    let rotation = (sin, cos);
+    arc_cubics(outline, transform, center, (rx, ry), rotation, theta, delta);
+}
+
+/// Turns path data into lines and cubics through the transform, quadratics
+/// and arcs included. Like browsers, whatever came before an error in the
+/// data is still drawn.
+fn outline(d: &str, transform: &Transform) -> Vec<Segment> {
+    use PathSegment::*;
+    let mut outline = vec![];
+    let (mut current, mut start) = ((0.0, 0.0), (0.0, 0.0));
+    // The control point a smooth curve reflects, and whether it was a
+    // cubic's.
+    let mut control: Option<(Point, bool)> = None;
+    let mut open = false;
+    for segment in PathParser::from(d) {
+        let segment = match segment {
+            Ok(segment) => segment,
+            Err(_) => break,
+        };
+        let absolute = |abs: bool, x: f64, y: f64| {
+            if abs {
+                (x, y)
+            } else {
+                (current.0 + x, current.1 + y)
+            }
+        };
+        let reflect =
+            |(x, y): Point| (2.0 * current.0 - x, 2.0 * current.1 - y);
+        // Drawing on after a close starts again where it ended.
+        if !open && !matches!(segment, MoveTo { .. } | ClosePath { .. }) {
+            outline.push(Segment::Move(apply(transform, current)));
+        }
+        open = !matches!(segment, ClosePath { .. });
+        let cubic = |outline: &mut Vec<Segment>, c1, c2, end| {
+            outline.push(Segment::Cubic(
+                apply(transform, c1),
+                apply(transform, c2),
+                apply(transform, end),
+            ))
+        };
+        // The cubic a quadratic is the same curve as.
+        let quadratic = |outline: &mut Vec<Segment>, c: Point, end: Point| {
+            let third = |from: Point| {
+                (
+                    from.0 + 2.0 / 3.0 * (c.0 - from.0),
+                    from.1 + 2.0 / 3.0 * (c.1 - from.1),
+                )
+            };
+            cubic(outline, third(current), third(end), end)
+        };
+        let (end, next_control) = match segment {
+            MoveTo { abs, x, y } => {
+                start = absolute(abs, x, y);
+                outline.push(Segment::Move(apply(transform, start)));
+                (start, None)
+            }
+            LineTo { abs, x, y } => (absolute(abs, x, y), None),
+            HorizontalLineTo { abs, x } => {
+                ((if abs { x } else { current.0 + x }, current.1), None)
+            }
+            VerticalLineTo { abs, y } => {
+                ((current.0, if abs { y } else { current.1 + y }), None)
+            }
+            CurveTo {
+                abs,
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                let (c1, c2) = (absolute(abs, x1, y1), absolute(abs, x2, y2));
+                let end = absolute(abs, x, y);
+                cubic(&mut outline, c1, c2, end);
+                (end, Some((c2, true)))
+            }
+            SmoothCurveTo { abs, x2, y2, x, y } => {
+                let c1 = match control {
+                    Some((c, true)) => reflect(c),
+                    _ => current,
+                };
+                let (c2, end) = (absolute(abs, x2, y2), absolute(abs, x, y));
+                cubic(&mut outline, c1, c2, end);
+                (end, Some((c2, true)))
+            }
+            Quadratic { abs, x1, y1, x, y } => {
+                let (c, end) = (absolute(abs, x1, y1), absolute(abs, x, y));
+                quadratic(&mut outline, c, end);
+                (end, Some((c, false)))
+            }
+            SmoothQuadratic { abs, x, y } => {
+                let c = match control {
+                    Some((c, false)) => reflect(c),
+                    _ => current,
+                };
+                let end = absolute(abs, x, y);
+                quadratic(&mut outline, c, end);
+                (end, Some((c, false)))
+            }
+            EllipticalArc {
+                abs,
+                rx,
+                ry,
+                x_axis_rotation,
+                large_arc,
+                sweep,
+                x,
+                y,
+            } => {
+                let end = absolute(abs, x, y);
+                arc(
+                    &mut outline,
+                    transform,
+                    current,
+                    (rx, ry),
+                    x_axis_rotation,
+                    large_arc,
+                    sweep,
+                    end,
+                );
+                (end, None)
+            }
+            ClosePath { .. } => {
+                outline.push(Segment::Close);
+                (start, None)
+            }
+        };
+        if matches!(
+            segment,
+            LineTo { .. } | HorizontalLineTo { .. } | VerticalLineTo { .. }
+        ) {
+            outline.push(Segment::Line(apply(transform, end)));
+        }
+        current = end;
+        control = next_control;
+    }
+    outline
+}
+
+/// A polyline in pixels.
+struct Contour {
+    points: Vec<Point>,
+    closed: bool,
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// How many lines a curve about `length` pixels long is cut into so that
+/// it still looks smooth.
+fn pieces(length: f64) -> usize {
+    (length.sqrt() * 2.0).ceil().clamp(1.0, 256.0) as usize
+}
+
+/// The outline as polylines, cubics cut into enough lines.
+fn flatten(outline: &[Segment]) -> Vec<Contour> {
+    let mut contours = vec![];
+    let mut points: Vec<Point> = vec![];
+    let mut flush = |points: &mut Vec<Point>, closed: bool| {
+        if points.len() > 1 {
+            contours.push(Contour {
+                points: std::mem::take(points),
+                closed,
+            });
+        }
+        points.clear();
+    };
+    for &segment in outline {
+        match segment {
+            Segment::Move(point) => {
+                flush(&mut points, false);
+                points.push(point);
+            }
+            Segment::Line(point) => points.push(point),
+            Segment::Cubic(p1, p2, p3) => {
+                let p0 = *points.last().unwrap_or(&p1);
+                let n = pieces(
+                    distance(p0, p1) + distance(p1, p2) + distance(p2, p3),
+                );
+                for i in 1..=n {
+                    let t = i as f64 / n as f64;
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (
+                        u * u * u,
+                        3.0 * u * u * t,
+                        3.0 * u * t * t,
+                        t * t * t,
+                    );
+                    points.push((
+                        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+                    ));
+                }
+            }
+            Segment::Close => flush(&mut points, true),
+        }
+    }
+    flush(&mut points, false);
+    contours
+}
+
+/// Path data for a basic shape, empty when there is nothing to draw.
+fn shape_path(node: &Node, (width, height): Point) -> String {
+    let diagonal = ((width * width + height * height) / 2.0).sqrt();
+    let get = |name: &str, reference: f64| length(node.get(name), reference);
+    match node.name {
+        "path" => node.get("d").unwrap_or_default().to_string(),
+        "rect" => {
+            let (x, y) = (get("x", width), get("y", height));
+            let (w, h) = (get("width", width), get("height", height));
+            if w <= 0.0 || h <= 0.0 {
+                return String::new();
+            }
+            // A missing radius is the same as the other one.
+            let (rx, ry) = match (node.get("rx"), node.get("ry")) {
+                (None, None) => (0.0, 0.0),
+                (Some(_), None) => (get("rx", width), get("rx", width)),
+                (None, Some(_)) => (get("ry", height), get("ry", height)),
+                (Some(_), Some(_)) => (get("rx", width), get("ry", height)),
+            };
+            let (rx, ry) = (rx.min(w / 2.0), ry.min(h / 2.0));
+            if rx > 0.0 && ry > 0.0 {
+                let corner = |x: f64, y: f64| {
+                    format!("A {} {} 0 0 1 {} {}", rx, ry, x, y)
+                };
+                format!(
+                    "M {} {} H {} {} V {} {} H {} {} V {} {} Z",
+                    x + rx,
+                    y,
+                    x + w - rx,
+                    corner(x + w, y + ry),
+                    y + h - ry,
+                    corner(x + w - rx, y + h),
+                    x + rx,
+                    corner(x, y + h - ry),
+                    y + ry,
+                    corner(x + rx, y),
+                )
+            } else {
+                format!("M {} {} H {} V {} H {} Z", x, y, x + w, y + h, x)
+            }
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (get("cx", width), get("cy", height));
+            let (rx, ry) = match node.name {
+                "circle" => (get("r", diagonal), get("r", diagonal)),
+                _ => (get("rx", width), get("ry", height)),
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return String::new();
+            }
+            format!(
+                "M {} {} A {} {} 0 1 0 {} {} A {} {} 0 1 0 {} {} Z",
+                cx - rx,
+                cy,
+                rx,
+                ry,
+                cx + rx,
+                cy,
+                rx,
+                ry,
+                cx - rx,
+                cy
+            )
+        }
+        "line" => format!(
+            "M {} {} L {} {}",
+            get("x1", width),
+            get("y1", height),
+            get("x2", width),
+            get("y2", height)
+        ),
+        _ => {
+            let points = PointsParser::from(node.get("points").unwrap_or(""))
+                .map(|(x, y)| format!("{} {}", x, y))
+                .collect::<Vec<_>>();
+            if points.is_empty() {
+                return String::new();
+            }
+            let close = if node.name == "polygon" { " Z" } else { "" };
+            format!("M {}{}", points.join(" L "), close)
+        }
+    }
+}
+
+/// The polygon turned so it winds the same way as every other one `stroke`
+/// makes, which lets them overlap without cancelling out.
+fn wound(mut points: Vec<Point>) -> Contour {
+    let area = (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<f64>();
+    if area < 0.0 {
+        points.reverse();
+    }
+    Contour {
+        points,
+        closed: true,
+    }
+}
+
+/// Polygons covering a line `width` pixels wide along each contour, with
+/// round joins and butt ends.
+fn stroke(contours: &[Contour], width: f64) -> Vec<Contour> {
+    let radius = width / 2.0;
+    let sides = pieces(std::f64::consts::TAU * radius).max(8);
+    let mut polygons = vec![];
+    for contour in contours {
+        let mut points = contour.points.clone();
+        if contour.closed {
+            points.push(points[0]);
+        }
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let length = distance(a, b);
+            if length == 0.0 {
+                continue;
+            }
+            let normal =
+                ((a.1 - b.1) / length * radius, (b.0 - a.0) / length * radius);
+            polygons.push(wound(vec![
+                (a.0 + normal.0, a.1 + normal.1),
+                (b.0 + normal.0, b.1 + normal.1),
+                (b.0 - normal.0, b.1 - normal.1),
+                (a.0 - normal.0, a.1 - normal.1),
+            ]));
+        }
+        let joins = if contour.closed {
+            &points[..]
+        } else {
+            &points[1..points.len() - 1]
+        };
+        for &(x, y) in joins {
+            polygons.push(wound(
+                (0..sides)
+                    .map(|i| {
+                        let angle =
+                            std::f64::consts::TAU * i as f64 / sides as f64;
+                        (x + radius * angle.cos(), y + radius * angle.sin())
+                    })
+                    .collect(),
+            ));
+        }
+    }
+    polygons
+}
+
+/// Signed area each edge adds to the pixels right of it, summed along rows
+/// into how much of a pixel is covered. Rows have two spare cells since
+/// edges on the right border still write past it.
+struct Coverage {
+    width: usize,
+    height: usize,
+    stride: usize,
+    area: Vec<f32>,
+}
+
+impl Coverage {
+    fn new(width: usize, height: usize) -> Self {
+        Coverage {
+            width,
+            height,
+            stride: width + 2,
+            area: vec![0.0; (width + 2) * height],
+        }
+    }
+
+    /// Adds an edge, pieces left or right of the region pressed onto its
+    /// border where they still change what's inside.
+    fn edge(&mut self, from: (f32, f32), to: (f32, f32)) {
+        let width = self.width as f32;
+        let mut cuts = vec![0.0, 1.0];
+        for bound in [0.0, width] {
+            let t = (bound - from.0) / (to.0 - from.0);
+            if t > 0.0 && t < 1.0 {
+                cuts.push(t);
+            }
+        }
+        cuts.sort_by(|a, b| a.total_cmp(b));
+        let at = |t: f32| {
+            (
+                (from.0 + (to.0 - from.0) * t).clamp(0.0, width),
+                from.1 + (to.1 - from.1) * t,
+            )
+        };
+        for pair in cuts.windows(2) {
+            self.line(at(pair[0]), at(pair[1]));
+        }
+    }
+
+    /// One edge with its x inside the region, after font-rs.
+    fn line(&mut self, p0: (f32, f32), p1: (f32, f32)) {
+        if (p0.1 - p1.1).abs() <= f32::EPSILON {
+            return;
+        }
+        let (direction, p0, p1) = if p0.1 < p1.1 {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+        let width = self.width as f32;
+        let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+        let mut x = p0.0;
+        if p0.1 < 0.0 {
+            x -= p0.1 * dxdy;
+        }
+        for y in p0.1.max(0.0) as usize..self.height.min(p1.1.ceil() as usize) {
+            let row = y * self.stride;
+            let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+            let next = (x + dxdy * dy).clamp(0.0, width);
+            let d = dy * direction;
+            let (x0, x1) = if x < next { (x, next) } else { (next, x) };
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            if x1i <= x0i + 1 {
+                let middle = 0.5 * (x + next) - x0_floor;
+                self.area[row + x0i] += d - d * middle;
+                self.area[row + x0i + 1] += d * middle;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                self.area[row + x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    self.area[row + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    self.area[row + x0i + 1] += d * (a1 - a0);
+                    for xi in x0i + 2..x1i - 1 {
+                        self.area[row + xi] += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.area[row + x1i - 1] += d * (1.0 - a2 - am);
+                }
+                self.area[row + x1i] += d * am;
+            }
+            x = next;
+        }
+    }
+}
+
+/// Something the shapes of an svg are drawn on, in its own coordinates.
+pub(crate) trait Surface {
+    fn fill(&mut self, outline: &[Segment], color: Color, opacity: f64);
+
+    /// A line `width` wide along the outline, with round joins.
+    fn stroke(
+        &mut self,
+        outline: &[Segment],
+        width: f64,
+        color: Color,
+        opacity: f64,
+    );
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    /// Premultiplied RGBA.
+    pixels: Vec<u8>,
+}
+
+impl Surface for Canvas {
+    fn fill(&mut self, outline: &[Segment], color: Color, opacity: f64) {
+        self.fill_contours(&flatten(outline), color, opacity);
+    }
+
+    fn stroke(
+        &mut self,
+        outline: &[Segment],
+        width: f64,
+        color: Color,
+        opacity: f64,
+    ) {
+        let polygons = stroke(&flatten(outline), width);
+        self.fill_contours(&polygons, color, opacity);
+    }
+}
+
+impl Canvas {
+    /// Fills the contours, anti-aliased, treating them as closed.
+    fn fill_contours(
+        &mut self,
+        contours: &[Contour],
+        color: Color,
+        opacity: f64,
+    ) {
+        let alpha = color.alpha as f32 / 255.0 * opacity as f32;
+        let points = contours.iter().flat_map(|contour| &contour.points);
+        if alpha <= 0.0
+            || points.clone().any(|p| !p.0.is_finite() || !p.1.is_finite())
+        {
+            return;
+        }
+        let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+        for point in points {
+            min = (min.0.min(point.0), min.1.min(point.1));
+            max = (max.0.max(point.0), max.1.max(point.1));
+        }
+        let x0 = (min.0.floor().max(0.0) as usize).min(self.width);
+        let y0 = (min.1.floor().max(0.0) as usize).min(self.height);
+        let x1 = (max.0.ceil().max(0.0) as usize).min(self.width);
+        let y1 = (max.1.ceil().max(0.0) as usize).min(self.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        let mut coverage = Coverage::new(x1 - x0, y1 - y0);
+        let local =
+            |(x, y): Point| ((x - x0 as f64) as f32, (y - y0 as f64) as f32);
+        for contour in contours {
+            let points = &contour.points;
+            for i in 0..points.len() {
+                let next = points[(i + 1) % points.len()];
+                coverage.edge(local(points[i]), local(next));
+            }
+        }
+        let source = [color.red, color.green, color.blue].map(|c| c as f32);
+        for y in 0..coverage.height {
+            let mut total = 0.0f32;
+            let row = &coverage.area[y * coverage.stride..];
+            for (x, area) in row[..coverage.width].iter().enumerate() {
+                total += area;
+                let a = total.abs().min(1.0) * alpha;
+                if a <= 0.0 {
+                    continue;
+                }
+                let at = ((y0 + y) * self.width + x0 + x) * 4;
+                let pixel = &mut self.pixels[at..at + 4];
+                for (channel, source) in pixel.iter_mut().zip(&source) {
+                    let value = source * a + *channel as f32 * (1.0 - a);
+                    *channel = value.round() as u8;
+                }
+                pixel[3] =
+                    (255.0 * a + pixel[3] as f32 * (1.0 - a)).round() as u8;
+            }
+        }
+    }
+}
+
+/// A parsed svg, ready to draw.
+pub(crate) struct Document<'a> {
+    nodes: Vec<Node<'a>>,
+    ids: HashMap<String, usize>,
+    /// Width and height of the root, in its units.
+    pub size: Point,
+    /// Size of the viewBox, which percentages are of.
+    viewport: Point,
+    /// From the viewBox to the root's size.
+    view: Transform,
+}
+
+impl<'a> Document<'a> {
+    pub fn parse(svg: &'a str) -> Result<Self, String> {
+        let nodes = parse(svg)?;
+        let root = nodes.first().ok_or("there is no root element")?;
+        if root.name != "svg" {
+            return Err(format!("it needs an <svg> root, not <{}>", root.name));
+        }
+        let view_box = root
+            .get("viewBox")
+            .and_then(|view_box| ViewBox::from_str(view_box).ok());
+        // Percentages of nothing fall back to the viewBox too.
+        let size = |name: &str, fallback: Option<f64>| {
+            root.get(name)
+                .filter(|value| !value.trim_end().ends_with('%'))
+                .map(|value| length(Some(value), 0.0))
+                .or(fallback)
+                .ok_or_else(|| format!("the svg has no {}", name))
+        };
+        let width = size("width", view_box.map(|view_box| view_box.w))?;
+        let height = size("height", view_box.map(|view_box| view_box.h))?;
+        let (viewport, view) = match view_box {
+            Some(view_box) => {
+                // Fitted in the middle, as preserveAspectRatio defaults to.
+                let fit = (width / view_box.w).min(height / view_box.h);
+                let view = Transform::new(
+                    fit,
+                    0.0,
+                    0.0,
+                    fit,
+                    (width - view_box.w * fit) / 2.0 - view_box.x * fit,
+                    (height - view_box.h * fit) / 2.0 - view_box.y * fit,
+                );
+                ((view_box.w, view_box.h), view)
+            }
+            None => ((width, height), Transform::default()),
+        };
+        let ids = nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| Some((node.get("id")?.to_string(), i)))
+            .collect();
+        Ok(Document {
+            nodes,
+            ids,
+            size: (width, height),
+            viewport,
+            view,
+        })
+    }
+
+    /// Draws everything, `transform` taking the root's units to the
+    /// surface's.
+    pub fn draw(&self, transform: &Transform, surface: &mut dyn Surface) {
+        let style = Style::new(multiply(transform, &self.view));
+        self.element(0, &style, 0, surface);
+    }
+
+    /// Draws the node at `at`, returning the index of the one after it.
+    fn element(
+        &self,
+        at: usize,
+        parent: &Style,
+        depth: usize,
+        surface: &mut dyn Surface,
+    ) -> usize {
+        let node = &self.nodes[at];
+        let (width, height) = self.viewport;
+        let diagonal = ((width * width + height * height) / 2.0).sqrt();
+        let style = match parent.child(node, diagonal) {
+            Some(style) => style,
+            None => return node.next,
+        };
+        match node.name {
+            "svg" | "g" | "a" | "switch" => {
+                let mut child = at + 1;
+                while child < node.next {
+                    child = self.element(child, &style, depth, surface);
+                }
+            }
+            "path" | "rect" | "circle" | "ellipse" | "line" | "polyline"
+            | "polygon" => {
+                let path = shape_path(node, self.viewport);
+                let outline = outline(&path, &style.transform);
+                if let Some(fill) = style.fill {
+                    let opacity = style.fill_opacity * style.opacity;
+                    surface.fill(&outline, fill, opacity);
+                }
+                let width = style.stroke_width * scale_of(&style.transform);
+                if let Some(color) = style.stroke.filter(|_| width > 0.0) {
+                    let opacity = style.stroke_opacity * style.opacity;
+                    surface.stroke(&outline, width, color, opacity);
+                }
+            }
+            // Uses of uses are followed a few deep, so loops end.
+            "use" if depth < 16 => {
+                let target = node
+                    .get("href")
+                    .or_else(|| node.get("xlink:href"))
+                    .and_then(|href| href.strip_prefix('#'))
+                    .and_then(|id| self.ids.get(id).copied());
+                if let Some(target) = target {
+                    let (x, y) = (
+                        length(node.get("x"), width),
+                        length(node.get("y"), height),
+                    );
+                    let mut style = style;
+                    style.transform = multiply(
+                        &style.transform,
+                        &Transform::new(1.0, 0.0, 0.0, 1.0, x, y),
+                    );
+                    self.element(target, &style, depth + 1, surface);
+                }
+            }
+            // Definitions, text and everything else that isn't drawn as
+            // it is.
+            _ => {}
+        }
+        node.next
+    }
+}
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let table = (0..256u32)
+        .map(|n| {
+            (0..8).fold(n, |c, _| {
+                if c & 1 == 1 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    !bytes.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Bits going out least significant first, as deflate wants.
+struct Bits {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn push(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// A huffman code, which goes out most significant bit first.
+    fn code(&mut self, code: u32, bits: u32) {
+        self.push(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    /// A symbol from the fixed literal and length codes.
+    fn symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+}
+
+const LENGTHS: [(u32, u32); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+const DISTANCES: [(u32, u32); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+/// One deflate block with the fixed codes, repeats found through a hash of
+/// the next three bytes.
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32768;
+    let mut bits = Bits {
+        out: vec![],
+        buffer: 0,
+        count: 0,
+    };
+    // Last block, fixed codes.
+    bits.push(1, 1);
+    bits.push(1, 2);
+    let hash = |i: usize| {
+        ((data[i] as usize) << 10
+            ^ (data[i + 1] as usize) << 5
+            ^ data[i + 2] as usize)
+            & (WINDOW - 1)
+    };
+    let mut head = vec![usize::MAX; WINDOW];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            previous[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if i + 3 <= data.len() {
+            let longest = (data.len() - i).min(258);
+            let mut candidate = head[hash(i)];
+            for _ in 0..16 {
+                if candidate == usize::MAX || i - candidate > WINDOW {
+                    break;
+                }
+                let matched = (0..longest)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                if matched > length {
+                    length = matched;
+                    distance = i - candidate;
+                    if matched == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate % WINDOW];
+            }
+        }
+        if length >= 3 {
+            let code = LENGTHS
+                .iter()
+                .rposition(|&(base, _)| base as usize <= length);
+            let code = code.unwrap();
+            let (base, extra) = LENGTHS[code];
+            bits.symbol(257 + code as u32);
+            bits.push(length as u32 - base, extra);
+            let code = DISTANCES
+                .iter()
+                .rposition(|&(base, _)| base as usize <= distance)
+                .unwrap();
+            let (base, extra) = DISTANCES[code];
+            bits.code(code as u32, 5);
+            bits.push(distance as u32 - base, extra);
+            for j in i..i + length {
+                insert(j, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            bits.symbol(data[i] as u32);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    bits.symbol(256);
+    bits.push(0, 7);
+    bits.out
+}
+
+/// The deflated data with zlib's header and checksum.
+pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn encode(canvas: &Canvas) -> Vec<u8> {
+    let mut raw = Vec::with_capacity((canvas.width * 4 + 1) * canvas.height);
+    for row in canvas.pixels.chunks(canvas.width * 4) {
+        // No filter.
+        raw.push(0);
+        for pixel in row.chunks(4) {
+            let alpha = pixel[3] as u32;
+            for &channel in &pixel[..3] {
+                raw.push(match alpha {
+                    0 => 0,
+                    _ => ((channel as u32 * 255 + alpha / 2) / alpha).min(255)
+                        as u8,
+                });
+            }
+            raw.push(pixel[3]);
+        }
+    }
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut chunk = |kind: &[u8], data: &[u8]| {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    };
+    let mut header = vec![];
+    header.extend_from_slice(&(canvas.width as u32).to_be_bytes());
+    header.extend_from_slice(&(canvas.height as u32).to_be_bytes());
+    // 8 bit RGBA, no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    chunk(b"IHDR", &header);
+    chunk(b"IDAT", &zlib(&raw));
+    chunk(b"IEND", &[]);
+    png
+}
+
+/// Draws the svg `scale` times its size and encodes it as a PNG, leaving
+/// whatever nothing covers transparent.
+pub fn png(svg: &str, scale: f64) -> Result<Vec<u8>, String> {
+    let document = Document::parse(svg)?;
+    let (width, height) = document.size;
+    let pixels = (
+        ((width * scale).ceil().max(1.0)) as usize,
+        ((height * scale).ceil().max(1.0)) as usize,
+    );
+    if pixels.0.saturating_mul(pixels.1) > 1 << 28 {
+        return Err(format!("{}x{} pixels is too large", pixels.0, pixels.1));
+    }
+    let mut canvas = Canvas {
+        width: pixels.0,
+        height: pixels.1,
+        pixels: vec![0; pixels.0 * pixels.1 * 4],
+    };
+    let transform = Transform::new(scale, 0.0, 0.0, scale, 0.0, 0.0);
+    document.draw(&transform, &mut canvas);
+    Ok(encode(&canvas))
+}
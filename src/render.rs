@@ -0,0 +1,2462 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use ttf_parser as ttf;
+use xmlwriter::*;
+
+use crate::{
+    bidi::{self, Direction},
+    builder::{Builder, Compact, Flatten, Holed},
+    color, compose,
+    error::Error,
+    outline::Outline,
+    YELLOW_WARNING,
+};
+
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub baseline_offset: f32,
+    pub glyph_per_file: bool,
+    /// Multiplier applied to every advance, the outlines are left alone.
+    pub advance_scale: f32,
+    pub bbox_fallback: bool,
+    pub embolden: f32,
+    /// Added after every glyph, in the primary face's units.
+    pub letter_spacing: f32,
+    /// Added after every space on top of the letter spacing.
+    pub word_spacing: f32,
+    pub metadata: Option<Metadata>,
+    /// Puts the text in a `<title>` and `aria-label`, for screen readers
+    /// and search.
+    pub embed_text: bool,
+    /// Wraps each glyph in a `<g>` saying which character it is.
+    pub annotate_glyphs: bool,
+    /// Color of a rectangle behind everything.
+    pub background: Option<String>,
+    /// Room left and right, and above and below, in the svg's own units.
+    pub padding: (f32, f32),
+    pub direction: Direction,
+    /// Each line's direction comes from its own text, `direction` only
+    /// for lines without a letter that has one.
+    pub direction_per_line: bool,
+    /// Mirrors every Bidi_Mirrored pair in right to left runs, not just
+    /// the common brackets.
+    pub mirror_brackets: bool,
+    /// Stacks the glyphs top to bottom instead of left to right.
+    pub vertical: bool,
+    /// Fixed width of the canvas in the svg's own units, padding aside.
+    pub canvas_width: Option<f32>,
+    pub align: Align,
+    /// Scales lines wider than the canvas down instead of letting them
+    /// overflow.
+    pub shrink_to_fit: bool,
+    /// Fixed height of the canvas, as tall as the line when not given.
+    pub canvas_height: Option<f32>,
+    /// Scales every line to the canvas, not just the ones too wide.
+    pub canvas_fit: Option<CanvasFit>,
+    /// Lines wider than this, in the svg's own units, wrap onto more lines.
+    pub max_width: Option<f32>,
+    /// Rules across the line from the font's post and OS/2 metrics.
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// CPAL palette color glyphs are drawn with.
+    pub palette: usize,
+    /// Draws color glyphs in the fill like any other, ignoring COLR.
+    pub mono_color_glyphs: bool,
+    /// Spaces between tab stops.
+    pub tab_width: usize,
+    /// Composes decomposed letters and centers leftover combining marks.
+    pub normalize: bool,
+    pub max_height: Option<f32>,
+    /// Largest advance a single glyph may have, in font units.
+    pub max_advance: Option<f32>,
+    pub round_trip_test: bool,
+    pub transform: Option<String>,
+    pub optimize_paths: bool,
+    pub gdef_spacing: bool,
+    pub kerning_source: KerningSource,
+    pub svg_version: Option<String>,
+    pub fix_winding: bool,
+    pub emit_baseline: bool,
+    /// Move characters to where they are drawn before looking up glyphs.
+    pub visual_order: bool,
+    pub fit_to_square: bool,
+    pub contour_limit: Option<usize>,
+    pub dedupe_paths: bool,
+    /// Curves become lines no further than this from them.
+    pub flatten_tolerance: Option<f32>,
+    /// Relative commands without the spaces they don't need.
+    pub compact_paths: bool,
+    /// Outline each glyph once into `<defs>` and `<use>` it everywhere.
+    pub use_defs: bool,
+    /// One path for all of a line's glyphs rather than one each.
+    pub merge_paths: bool,
+    pub root_tag: RootTag,
+    pub glyph_origin: GlyphOrigin,
+    /// Mark spaces with a dot and tabs with an arrow.
+    pub whitespace_visible: bool,
+    /// Drawn instead of characters no face has.
+    pub placeholder: Option<char>,
+    /// A box with a cross for `.notdef` glyphs the font left empty.
+    pub notdef_box: bool,
+    pub line_height: LineHeight,
+    /// `--antialias-hint none`: crispEdges plus rounded coordinates.
+    pub crisp_edges: bool,
+    pub adaptive_precision: bool,
+    pub fill: Option<String>,
+    pub stroke: Option<String>,
+    pub stroke_width: Option<f32>,
+    /// `miter`, `round` or `bevel`, and `butt`, `round` or `square`.
+    pub stroke_linejoin: Option<String>,
+    pub stroke_linecap: Option<String>,
+    /// Written before the root element.
+    pub comment: Option<String>,
+    /// How elements are indented, 4 spaces unless --xml-indent says.
+    pub indent: Indent,
+    /// Attributes in `"` rather than `'`.
+    pub double_quotes: bool,
+    /// Starts the file with `<?xml version="1.0" encoding="UTF-8"?>`.
+    pub xml_declaration: bool,
+    /// Decimal places every coordinate is rounded to.
+    pub precision: Option<i32>,
+    /// Pixels per em, when font units shouldn't be used as is.
+    pub size: Option<f32>,
+    /// Canvas every svg is padded out to, centered.
+    pub cell: Option<(f32, f32)>,
+    /// Sizes the canvas to the glyphs' ink rather than their advances and
+    /// the line height.
+    pub tight_bounds: bool,
+    /// Multiplies the distance from one stacked line's baseline to the
+    /// next.
+    pub line_height_factor: f32,
+    /// Stacked lines all a line height of the tallest apart, for an even
+    /// baseline grid.
+    pub baseline_grid: bool,
+    /// Prefix of the classes each line's group in a document gets, `line
+    /// line-3` for `line`.
+    pub line_class: Option<String>,
+    /// Case mapping the lines are drawn with.
+    pub case: Option<Case>,
+    /// How big small caps are next to the capitals.
+    pub small_caps_scale: f32,
+    pub outlines: OutlineCache,
+}
+
+/// What the command line gives without any options.
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            baseline_offset: 0.0,
+            glyph_per_file: false,
+            advance_scale: 1.0,
+            bbox_fallback: false,
+            embolden: 0.0,
+            letter_spacing: 0.0,
+            word_spacing: 0.0,
+            metadata: None,
+            embed_text: false,
+            annotate_glyphs: false,
+            background: None,
+            padding: (0.0, 0.0),
+            direction: Direction::Auto,
+            direction_per_line: false,
+            mirror_brackets: false,
+            vertical: false,
+            canvas_width: None,
+            align: Align::Left,
+            shrink_to_fit: false,
+            canvas_height: None,
+            canvas_fit: None,
+            max_width: None,
+            underline: false,
+            strikethrough: false,
+            palette: 0,
+            mono_color_glyphs: false,
+            tab_width: 4,
+            normalize: true,
+            max_height: None,
+            max_advance: None,
+            round_trip_test: false,
+            transform: None,
+            optimize_paths: false,
+            gdef_spacing: false,
+            kerning_source: KerningSource::Auto,
+            svg_version: None,
+            fix_winding: false,
+            emit_baseline: false,
+            visual_order: false,
+            fit_to_square: false,
+            contour_limit: None,
+            dedupe_paths: false,
+            flatten_tolerance: None,
+            compact_paths: false,
+            use_defs: false,
+            merge_paths: false,
+            root_tag: RootTag::Svg,
+            glyph_origin: GlyphOrigin::Baseline,
+            whitespace_visible: false,
+            placeholder: None,
+            notdef_box: false,
+            line_height: LineHeight::Ascent,
+            crisp_edges: false,
+            adaptive_precision: false,
+            fill: None,
+            stroke: None,
+            stroke_width: None,
+            stroke_linejoin: None,
+            stroke_linecap: None,
+            comment: None,
+            indent: Indent::Spaces(4),
+            double_quotes: false,
+            xml_declaration: false,
+            precision: None,
+            size: None,
+            cell: None,
+            tight_bounds: false,
+            line_height_factor: 1.0,
+            baseline_grid: false,
+            line_class: None,
+            case: None,
+            small_caps_scale: 0.75,
+            outlines: OutlineCache::default(),
+        }
+    }
+}
+
+/// How a line's case is mapped before its glyphs are looked up.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// Upper case at the start of each word and lower case after.
+    Title,
+    /// Lowercase letters drawn with their capitals, scaled down.
+    SmallCaps,
+}
+
+impl Case {
+    /// The characters glyphs are looked up for, which can be more than
+    /// there were, ß becoming SS.
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Case::Upper => text.to_uppercase(),
+            Case::Lower => text.to_lowercase(),
+            Case::Title => {
+                let mut mapped = String::with_capacity(text.len());
+                let mut start = true;
+                for c in text.chars() {
+                    if start {
+                        mapped.extend(c.to_uppercase());
+                    } else {
+                        mapped.extend(c.to_lowercase());
+                    }
+                    // An apostrophe doesn't start a word, so "don't" stays
+                    // one.
+                    start = !(c.is_alphanumeric() || matches!(c, '\'' | '’'));
+                }
+                mapped
+            }
+            Case::SmallCaps => small_caps(text.chars(), Some(1.0))
+                .map(|(c, _)| c)
+                .collect(),
+        }
+    }
+}
+
+/// Each character with how much it's scaled, small caps at `scale` giving
+/// lowercase letters' capitals instead.
+fn small_caps(
+    chars: impl IntoIterator<Item = char>,
+    scale: Option<f32>,
+) -> impl Iterator<Item = (char, f32)> {
+    chars.into_iter().flat_map(move |c| {
+        let small = scale.filter(|_| c.is_lowercase());
+        let capitals = c.to_uppercase().filter(move |_| small.is_some());
+        std::iter::once(c)
+            .filter(move |_| small.is_none())
+            .chain(capitals)
+            .map(move |c| (c, small.unwrap_or(1.0)))
+    })
+}
+
+/// Glyphs read from the font the first time they're drawn and reused for
+/// every line after, from any thread. Clones share it.
+#[derive(Clone, Default)]
+pub struct OutlineCache {
+    /// In font units, `None` for glyphs without an outline. Also keyed by
+    /// the glyph's units, as bits, which emboldening depends on.
+    outlines: Cache<(FaceKey, u16, u32), Option<Outline>>,
+    /// Absolute path data with holes for the x coordinates, which are all
+    /// that depend on where the glyph is. Also keyed by the units, baseline
+    /// and scale, as bits.
+    paths: Cache<(FaceKey, u16, u32, u32, u32), Holed>,
+    /// Outline lookups that found the glyph and that had to read it.
+    hits: Arc<AtomicUsize>,
+    misses: Arc<AtomicUsize>,
+}
+
+/// How the outline cache did, for `--glyph-cache-stats`.
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    /// Distinct glyphs read, one per face, variation and size.
+    pub entries: usize,
+}
+
+impl OutlineCache {
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.outlines.read().unwrap().len(),
+        }
+    }
+}
+
+type Cache<K, V> = Arc<RwLock<HashMap<K, Arc<V>>>>;
+
+/// The face's address and a hash of its variation coordinates, so setting
+/// a variation between lines doesn't give back the old outlines.
+type FaceKey = (usize, u64);
+
+fn face_key(face: &ttf::Face) -> FaceKey {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for coordinate in face.variation_coordinates() {
+        coordinate.get().hash(&mut hasher);
+    }
+    (face as *const ttf::Face as usize, hasher.finish())
+}
+
+/// Scales that vary line by line could otherwise fill memory with paths
+/// that are never used again.
+const MAX_CACHED_PATHS: usize = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RootTag {
+    Svg,
+    /// For sprites, sized with a viewBox.
+    Symbol,
+    /// For inlining into another svg.
+    G,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GlyphOrigin {
+    /// Where the font puts the glyph, on the baseline.
+    Baseline,
+    /// The glyph's bounding box centered in its advance and the line, for
+    /// icon fonts.
+    Center,
+}
+
+/// What the height of a line and its baseline are measured from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineHeight {
+    /// The font's ascender, descender and line gap.
+    Ascent,
+    /// The cap height down to the descender, without the line gap.
+    Cap,
+    /// The ink of the line's glyphs, tightest of all.
+    Bbox,
+}
+
+/// Where lines narrower than the canvas go.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How a line is scaled to a `canvas_width` canvas.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CanvasFit {
+    /// As large as fits entirely inside, leaving bands where it doesn't
+    /// reach.
+    Contain,
+    /// As small as fills the whole canvas, the overflow clipped.
+    Cover,
+}
+
+/// Which of the font's tables kerning comes from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum KerningSource {
+    /// GPOS when the font has a `kern` feature there, the `kern` table
+    /// otherwise, so fonts with both aren't kerned twice.
+    Auto,
+    Kern,
+    Gpos,
+    Both,
+}
+
+/// A glyph along with the face it was found in.
+#[derive(Clone, Copy)]
+pub struct Glyph<'a> {
+    pub face: &'a ttf::Face<'a>,
+    pub id: ttf::GlyphId,
+    /// Converts the face's units to those of the primary face, in case
+    /// their units per em differ.
+    pub units: f32,
+    /// The character it was looked up for.
+    pub c: char,
+}
+
+/// A glyph of the primary face.
+fn lookup_in<'a>(face: &'a ttf::Face<'a>, id: ttf::GlyphId) -> Glyph<'a> {
+    Glyph {
+        face,
+        id,
+        units: 1.0,
+        c: ' ',
+    }
+}
+
+/// Width in ems of the Unicode spaces, for fonts without a glyph of their
+/// own for one.
+fn space_width(c: char) -> Option<f32> {
+    Some(match c {
+        '\u{00A0}' => 0.25,
+        '\u{2000}' | '\u{2002}' => 0.5,
+        '\u{2001}' | '\u{2003}' | '\u{3000}' => 1.0,
+        '\u{2004}' => 1.0 / 3.0,
+        '\u{2005}' => 0.25,
+        '\u{2006}' => 1.0 / 6.0,
+        '\u{2007}' => 0.55,
+        '\u{2008}' | '\u{2009}' | '\u{202F}' => 0.2,
+        '\u{200A}' => 0.1,
+        '\u{205F}' => 4.0 / 18.0,
+        _ => return None,
+    })
+}
+
+/// Whether the glyph is a combining mark that takes no room of its own and
+/// goes over the one before.
+fn centers_mark(glyph: Glyph, options: &RenderOptions) -> bool {
+    options.normalize && compose::is_combining(glyph.c)
+}
+
+/// Tabs and the Unicode spaces, which are laid out whether or not the font
+/// has a glyph for them.
+pub fn is_blank(c: char) -> bool {
+    c == '\t' || space_width(c).is_some()
+}
+
+/// How wide a glyph that's only there for its space is, in ems: tabs,
+/// which get a width from `layout`, and spaces the font lacks.
+fn blank_width(glyph: Glyph) -> Option<f32> {
+    if glyph.c == '\t' {
+        Some(0.0)
+    } else if glyph.id.0 == 0 {
+        space_width(glyph.c)
+    } else {
+        None
+    }
+}
+
+/// Looks the character up in each face in turn, the first being the
+/// primary one.
+pub fn lookup<'a>(faces: &'a [ttf::Face<'a>], c: char) -> Option<Glyph<'a>> {
+    let primary = faces.first()?;
+    faces.iter().find_map(|face| {
+        Some(Glyph {
+            face,
+            id: face.glyph_index(c)?,
+            units: primary.units_per_em() as f32 / face.units_per_em() as f32,
+            c,
+        })
+    })
+}
+
+#[derive(Clone)]
+pub struct Metadata {
+    pub font_family: String,
+    /// Seconds since the unix epoch, shared by every file of a run.
+    pub created: u64,
+}
+
+/// Without shaping, Indic pre-base matras come out after the consonant
+/// they are written in front of. This moves each one before its cluster,
+/// the consonant plus any consonant and virama pairs leading up to it.
+pub fn visual_order(text: &str) -> Vec<char> {
+    let is_pre_base_matra = |c: char| {
+        matches!(
+            c,
+            '\u{093F}' // Devanagari i
+                | '\u{09BF}' | '\u{09C7}' | '\u{09C8}' // Bengali
+                | '\u{0A3F}' // Gurmukhi
+                | '\u{0ABF}' // Gujarati
+                | '\u{0B47}' // Oriya
+                | '\u{0BC6}'..='\u{0BC8}' // Tamil
+                | '\u{0D46}'..='\u{0D48}' // Malayalam
+                | '\u{0DD9}'..='\u{0DDB}' // Sinhala
+        )
+    };
+    let is_virama = |c: char| {
+        matches!(
+            c,
+            '\u{094D}'
+                | '\u{09CD}'
+                | '\u{0A4D}'
+                | '\u{0ACD}'
+                | '\u{0B4D}'
+                | '\u{0BCD}'
+                | '\u{0D4D}'
+                | '\u{0DCA}'
+        )
+    };
+    let mut chars: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if is_pre_base_matra(c) {
+            let mut at = chars.len().saturating_sub(1);
+            while at >= 2 && is_virama(chars[at - 1]) {
+                at -= 2;
+            }
+            chars.insert(at, c);
+        } else {
+            chars.push(c);
+        }
+    }
+    chars
+}
+
+pub fn family_name(face: &ttf::Face) -> Option<String> {
+    face.names()
+        .into_iter()
+        .filter(|name| name.name_id == ttf::name_id::FAMILY)
+        .find_map(|name| name.to_string())
+}
+
+fn is_mark(face: &ttf::Face, glyph_id: ttf::GlyphId) -> bool {
+    face.tables()
+        .gdef
+        .and_then(|gdef| gdef.glyph_class(glyph_id))
+        == Some(ttf::gdef::GlyphClass::Mark)
+}
+
+fn advance(glyph: Glyph, options: &RenderOptions) -> f32 {
+    let Glyph {
+        face, id: glyph_id, ..
+    } = glyph;
+    if options.gdef_spacing && is_mark(face, glyph_id) {
+        return 0.0;
+    }
+    let advance = face.glyph_hor_advance(glyph_id).unwrap_or_default();
+    let advance = if options.bbox_fallback
+        && (advance == 0 || advance > face.units_per_em() * 2)
+    {
+        // Broken hmtx entries get the ink width plus a twentieth of an em
+        // on either side, which roughly matches typical side bearings.
+        match face.glyph_bounding_box(glyph_id) {
+            Some(bbox) => {
+                bbox.width() as f32 + face.units_per_em() as f32 / 10.0
+            }
+            None => advance as f32,
+        }
+    } else {
+        advance as f32
+    };
+    let advance = advance * glyph.units;
+    let advance = match options.max_advance {
+        Some(max_advance) if advance > max_advance => {
+            eprintln!(
+                "{}glyph {} advance of {} clamped to {}.",
+                YELLOW_WARNING, glyph_id.0, advance, max_advance
+            );
+            max_advance
+        }
+        _ => advance,
+    };
+    let spacing = if face.glyph_index(' ') == Some(glyph_id) {
+        options.letter_spacing + options.word_spacing
+    } else {
+        options.letter_spacing
+    };
+    (advance + options.embolden) * options.advance_scale + spacing
+}
+
+/// Horizontal adjustment between a pair of glyphs from the tables the
+/// source picks.
+fn kerning(
+    face: &ttf::Face,
+    left: ttf::GlyphId,
+    right: ttf::GlyphId,
+    options: &RenderOptions,
+) -> f32 {
+    let gpos = || gpos_kerning(face, left, right).unwrap_or_default();
+    let kern = || kern_kerning(face, left, right);
+    match options.kerning_source {
+        KerningSource::Auto if gpos_kerns(face) => gpos(),
+        KerningSource::Auto | KerningSource::Kern => kern(),
+        KerningSource::Gpos => gpos(),
+        KerningSource::Both => gpos() + kern(),
+    }
+}
+
+/// Whether the font has a GPOS `kern` feature to take kerning from.
+fn gpos_kerns(face: &ttf::Face) -> bool {
+    let kern = ttf::Tag::from_bytes(b"kern");
+    face.tables()
+        .gpos
+        .is_some_and(|table| table.features.into_iter().any(|f| f.tag == kern))
+}
+
+/// The pair adjustment GPOS `kern` lookups give the glyphs, `None` when
+/// the font has no GPOS.
+fn gpos_kerning(
+    face: &ttf::Face,
+    left: ttf::GlyphId,
+    right: ttf::GlyphId,
+) -> Option<f32> {
+    use ttf::gpos::{PairAdjustment, PositioningSubtable};
+    let table = face.tables().gpos?;
+    let kern = ttf::Tag::from_bytes(b"kern");
+    // Each script can have a kern feature, usually sharing lookups.
+    let mut lookups = table
+        .features
+        .into_iter()
+        .filter(|feature| feature.tag == kern)
+        .flat_map(|feature| feature.lookup_indices)
+        .collect::<Vec<_>>();
+    lookups.sort_unstable();
+    lookups.dedup();
+    let mut kerning = 0.0;
+    for index in lookups {
+        let lookup = match table.lookups.get(index) {
+            Some(lookup) => lookup,
+            None => continue,
+        };
+        // The first subtable with the pair in it is the one that applies.
+        let records = lookup
+            .subtables
+            .into_iter::<PositioningSubtable>()
+            .find_map(|subtable| {
+                let pair = match subtable {
+                    PositioningSubtable::Pair(pair) => pair,
+                    _ => return None,
+                };
+                let first = pair.coverage().get(left)?;
+                match pair {
+                    PairAdjustment::Format1 { sets, .. } => {
+                        sets.get(first)?.get(right)
+                    }
+                    PairAdjustment::Format2 {
+                        classes, matrix, ..
+                    } => {
+                        matrix.get((classes.0.get(left), classes.1.get(right)))
+                    }
+                }
+            });
+        if let Some((record, _)) = records {
+            kerning += f32::from(record.x_advance);
+        }
+    }
+    Some(kerning)
+}
+
+/// Horizontal adjustment between a pair of glyphs from the `kern` table,
+/// zero when the font has none.
+fn kern_kerning(
+    face: &ttf::Face,
+    left: ttf::GlyphId,
+    right: ttf::GlyphId,
+) -> f32 {
+    let table = match face.tables().kern {
+        Some(table) => table,
+        None => return 0.0,
+    };
+    table
+        .subtables
+        .into_iter()
+        .filter(|subtable| {
+            subtable.horizontal
+                && !subtable.variable
+                && !subtable.has_cross_stream
+        })
+        .filter_map(|subtable| subtable.glyphs_kerning(left, right))
+        .map(f32::from)
+        .sum()
+}
+
+/// Mean of the non-zero advances in the font, so marks don't drag it down.
+pub fn average_advance(face: &ttf::Face) -> f32 {
+    let advances = (0..face.number_of_glyphs())
+        .filter_map(|id| face.glyph_hor_advance(ttf::GlyphId(id)))
+        .filter(|&advance| advance > 0)
+        .map(f32::from)
+        .collect::<Vec<_>>();
+    if advances.is_empty() {
+        return face.units_per_em() as f32;
+    }
+    advances.iter().sum::<f32>() / advances.len() as f32
+}
+
+/// The glyph's outline with the options that change its shape applied,
+/// warning about whatever had to be fixed or left out.
+fn read_outline(glyph: Glyph, options: &RenderOptions) -> Option<Outline> {
+    let Glyph {
+        face, id: glyph_id, ..
+    } = glyph;
+    let mut outline = Outline::default();
+    if face.outline_glyph(glyph_id, &mut outline).is_none() {
+        if !options.notdef_box || glyph_id.0 != 0 {
+            return None;
+        }
+        outline = notdef_box(face);
+    }
+    match options.contour_limit {
+        Some(limit) if outline.contours.len() > limit => {
+            // Real glyphs rarely come close, so this is most likely a
+            // corrupt font and the glyph is left out.
+            eprintln!(
+                "{}glyph {} has {} contours, more than the limit of {}, and \
+                 was skipped.",
+                YELLOW_WARNING,
+                glyph_id.0,
+                outline.contours.len(),
+                limit
+            );
+            return None;
+        }
+        _ => {}
+    }
+    // Fix the winding first, emboldening depends on it being right.
+    if options.fix_winding && outline.fix_winding() {
+        eprintln!(
+            "{}glyph {} had inconsistent winding and was corrected.",
+            YELLOW_WARNING, glyph_id.0
+        );
+    }
+    if options.embolden != 0.0 {
+        // The amount is in the primary face's units.
+        outline.embolden(options.embolden / glyph.units);
+    }
+    if options.optimize_paths {
+        outline.optimize();
+    }
+    Some(outline)
+}
+
+/// `read_outline` the first time a glyph is drawn, from the cache after.
+fn cached_outline(
+    glyph: Glyph,
+    options: &RenderOptions,
+) -> Arc<Option<Outline>> {
+    let cache = &options.outlines;
+    let key = (face_key(glyph.face), glyph.id.0, glyph.units.to_bits());
+    let cached = cache.outlines.read().unwrap().get(&key).cloned();
+    let counter = match cached {
+        Some(_) => &cache.hits,
+        None => &cache.misses,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    cached.unwrap_or_else(|| {
+        let outline = Arc::new(read_outline(glyph, options));
+        cache.outlines.write().unwrap().insert(key, outline.clone());
+        outline
+    })
+}
+
+fn outline(
+    glyph: Glyph,
+    options: &RenderOptions,
+    builder: &mut Builder,
+) -> Option<()> {
+    let Glyph {
+        face, id: glyph_id, ..
+    } = glyph;
+    let offset = builder.offset;
+    let cache = &options.outlines;
+    let outline = cached_outline(glyph, options);
+    let outline = outline.as_ref().as_ref()?;
+    if options.embolden != 0.0 {
+        // Keep the left side bearing, the extra width goes into the
+        // advance.
+        builder.offset += options.embolden / 2.0;
+    }
+    if builder.compact.is_some() {
+        // Relative commands depend on where the glyph is throughout.
+        outline.replay(builder);
+    } else {
+        let key = (
+            face_key(face),
+            glyph_id.0,
+            builder.units.to_bits(),
+            builder.baseline_offset.to_bits(),
+            builder.scale.to_bits(),
+        );
+        let cached = cache.paths.read().unwrap().get(&key).cloned();
+        let path = match cached {
+            Some(path) => path,
+            None => {
+                let mut recorder = Builder {
+                    buffer: String::new(),
+                    offset: 0.0,
+                    baseline_offset: builder.baseline_offset,
+                    scale: builder.scale,
+                    units: builder.units,
+                    round: builder.round,
+                    precision: builder.precision,
+                    compact: None,
+                    flatten: options.flatten_tolerance.map(Flatten::new),
+                    holes: Some(vec![]),
+                };
+                outline.replay(&mut recorder);
+                let path = Arc::new(Holed {
+                    data: recorder.buffer,
+                    holes: recorder.holes.unwrap(),
+                });
+                let mut paths = cache.paths.write().unwrap();
+                if paths.len() < MAX_CACHED_PATHS {
+                    paths.insert(key, path.clone());
+                }
+                path
+            }
+        };
+        builder.fill(&path);
+    }
+    if options.precision.is_some() {
+        let len = builder.buffer.trim_end().len();
+        builder.buffer.truncate(len);
+    }
+    if options.round_trip_test {
+        check_round_trip(glyph, options, offset, builder);
+    }
+    Some(())
+}
+
+/// Bounds of every point in a path, control points included, as
+/// `(x_min, y_min, x_max, y_max)`.
+fn path_bounds(path: &str) -> Option<(f64, f64, f64, f64)> {
+    use svgtypes::PathSegment::*;
+    let mut bounds: Option<(f64, f64, f64, f64)> = None;
+    let mut add = |x: f64, y: f64| {
+        let (x_min, y_min, x_max, y_max) = bounds.unwrap_or((x, y, x, y));
+        bounds = Some((x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y)));
+    };
+    let (mut current, mut start) = ((0.0, 0.0), (0.0, 0.0));
+    for segment in svgtypes::PathParser::from(path) {
+        let segment = segment.ok()?;
+        let absolute = |abs: bool, x: f64, y: f64| {
+            if abs {
+                (x, y)
+            } else {
+                (current.0 + x, current.1 + y)
+            }
+        };
+        let end = match segment {
+            MoveTo { abs, x, y } => {
+                start = absolute(abs, x, y);
+                start
+            }
+            LineTo { abs, x, y } | SmoothQuadratic { abs, x, y } => {
+                absolute(abs, x, y)
+            }
+            HorizontalLineTo { abs, x } => {
+                (if abs { x } else { current.0 + x }, current.1)
+            }
+            VerticalLineTo { abs, y } => {
+                (current.0, if abs { y } else { current.1 + y })
+            }
+            Quadratic { abs, x1, y1, x, y } => {
+                let (x1, y1) = absolute(abs, x1, y1);
+                add(x1, y1);
+                absolute(abs, x, y)
+            }
+            CurveTo {
+                abs,
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                let (x1, y1) = absolute(abs, x1, y1);
+                let (x2, y2) = absolute(abs, x2, y2);
+                add(x1, y1);
+                add(x2, y2);
+                absolute(abs, x, y)
+            }
+            SmoothCurveTo { abs, x2, y2, x, y } => {
+                let (x2, y2) = absolute(abs, x2, y2);
+                add(x2, y2);
+                absolute(abs, x, y)
+            }
+            EllipticalArc { abs, x, y, .. } => absolute(abs, x, y),
+            ClosePath { .. } => start,
+        };
+        add(end.0, end.1);
+        current = end;
+    }
+    bounds
+}
+
+/// Parses the path that was just written back and warns when it doesn't
+/// land where the font's bounding box says the glyph should be.
+fn check_round_trip(
+    glyph: Glyph,
+    options: &RenderOptions,
+    offset: f32,
+    builder: &Builder,
+) {
+    let glyph_id = glyph.id;
+    let bbox = match glyph.face.glyph_bounding_box(glyph_id) {
+        Some(bbox) => bbox,
+        None => return,
+    };
+    let to_svg = |x: i16, y: i16| {
+        let (x, y) = (x as f32 * glyph.units, y as f32 * glyph.units);
+        (
+            ((x + offset) * builder.scale) as f64,
+            (builder.baseline_offset - y * builder.scale) as f64,
+        )
+    };
+    let (x_min, y_min) = to_svg(bbox.x_min, bbox.y_max);
+    let (x_max, y_max) = to_svg(bbox.x_max, bbox.y_min);
+    let tolerance = ((0.5 + options.embolden) * builder.scale) as f64 + 0.01;
+    let matches = match path_bounds(&builder.buffer) {
+        Some(bounds) => [
+            (bounds.0, x_min),
+            (bounds.1, y_min),
+            (bounds.2, x_max),
+            (bounds.3, y_max),
+        ]
+        .iter()
+        .all(|(actual, expected)| (actual - expected).abs() <= tolerance),
+        None => false,
+    };
+    if !matches {
+        eprintln!(
+            "{}glyph {} does not match its bounding box after writing.",
+            YELLOW_WARNING, glyph_id.0
+        );
+    }
+}
+
+/// Renders a line of text to a finished svg document. Characters missing
+/// from the first face are looked for in the rest, so there has to be at
+/// least one.
+pub fn render_line(
+    faces: &[ttf::Face],
+    text: &str,
+    options: &RenderOptions,
+) -> Result<String, Error> {
+    let glyphs = glyphs(faces, text, options);
+    Ok(render(&faces[0], &glyphs, options, "line", text).0)
+}
+
+/// Glyphs for the text, in the order they are laid out. Characters no face
+/// has are drawn with the primary face's `.notdef` glyph.
+pub fn glyphs<'a>(
+    faces: &'a [ttf::Face<'a>],
+    text: &str,
+    options: &RenderOptions,
+) -> Vec<Glyph<'a>> {
+    let mapped: String;
+    let text = match options.case {
+        // Small caps need to know which letters were lowercase, so are
+        // mapped as they're looked up.
+        Some(case) if case != Case::SmallCaps => {
+            mapped = case.apply(text);
+            &mapped
+        }
+        _ => text,
+    };
+    let composed: String;
+    let text = if options.normalize {
+        let chars = compose::compose(text, |c| lookup(faces, c).is_some());
+        composed = chars.into_iter().collect();
+        &composed
+    } else {
+        text
+    };
+    let chars = if options.visual_order {
+        visual_order(text)
+    } else {
+        text.chars().collect()
+    };
+    let direction = if options.direction_per_line {
+        bidi::detect(&chars).unwrap_or(options.direction)
+    } else {
+        options.direction
+    };
+    let chars = bidi::visual(chars, direction, options.mirror_brackets);
+    let scale = match options.case {
+        Some(Case::SmallCaps) => Some(options.small_caps_scale),
+        _ => None,
+    };
+    small_caps(chars, scale)
+        .filter_map(|(c, scale)| {
+            let mut glyph = lookup(faces, c)
+                .or_else(|| {
+                    // Spaces are given a width rather than a stand in.
+                    if is_blank(c) {
+                        return None;
+                    }
+                    lookup(faces, options.placeholder?)
+                })
+                .or_else(|| {
+                    // Drawn as a `notdef_box` when the font's is empty.
+                    Some(Glyph {
+                        face: faces.first()?,
+                        id: ttf::GlyphId(0),
+                        units: 1.0,
+                        c,
+                    })
+                })?;
+            glyph.units *= scale;
+            Some(glyph)
+        })
+        .collect()
+}
+
+/// A box with a cross through it, as wide as the `.notdef` advance and as
+/// tall as a capital, in font units.
+fn notdef_box(face: &ttf::Face) -> Outline {
+    use ttf::OutlineBuilder;
+    let em = face.units_per_em() as f32;
+    let advance = face
+        .glyph_hor_advance(ttf::GlyphId(0))
+        .filter(|&advance| advance > 0)
+        .map_or(em / 2.0, f32::from);
+    let height = face
+        .capital_height()
+        .filter(|&height| height > 0)
+        .map_or(em * 0.7, f32::from);
+    let stroke = em / 20.0;
+    let (left, right, top) = (stroke, advance - stroke, height);
+    let (l, r, b, t) = (left + stroke, right - stroke, stroke, top - stroke);
+    let mut outline = Outline::default();
+    let mut polygon = |points: &[(f32, f32)]| {
+        outline.move_to(points[0].0, points[0].1);
+        for &(x, y) in &points[1..] {
+            outline.line_to(x, y);
+        }
+        outline.close();
+    };
+    // Clockwise like any TrueType outline, the inside the other way round
+    // so it's left empty.
+    polygon(&[(left, 0.0), (left, top), (right, top), (right, 0.0)]);
+    if r - l > stroke && t - b > stroke {
+        polygon(&[(l, b), (r, b), (r, t), (l, t)]);
+        polygon(&[(l, b), (r - stroke, t), (r, t), (l + stroke, b)]);
+        polygon(&[(r - stroke, b), (l, t), (l + stroke, t), (r, b)]);
+    }
+    outline
+}
+
+/// Where the glyphs go and how big they are, before any padding.
+#[derive(Clone)]
+struct Layout {
+    /// In the primary face's units, kerning included.
+    advances: Vec<f32>,
+    ascender: f32,
+    /// Below zero, like the font's.
+    descender: f32,
+    scale: f32,
+    /// Scaled size of the content.
+    width: f32,
+    height: f32,
+}
+
+fn layout(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+) -> Layout {
+    // By default every line gets the font's full line height, whatever its
+    // glyphs, with the baseline an ascender down from the top. Fallback
+    // faces can only make it taller.
+    let metrics = |face: &ttf::Face| {
+        let ascender = match options.line_height {
+            // Older OS/2 tables have no cap height, the top of H is close.
+            LineHeight::Cap => face
+                .capital_height()
+                .or_else(|| {
+                    let h = face.glyph_index('H')?;
+                    Some(face.glyph_bounding_box(h)?.y_max)
+                })
+                .unwrap_or(face.ascender()),
+            _ => face.ascender(),
+        };
+        let line_gap = match options.line_height {
+            LineHeight::Ascent => face.line_gap(),
+            _ => 0,
+        };
+        (ascender as f32, face.descender() as f32, line_gap as f32)
+    };
+    let (mut ascender, mut descender, mut line_gap) = metrics(face);
+    for glyph in glyphs {
+        let (glyph_ascender, glyph_descender, glyph_line_gap) =
+            metrics(glyph.face);
+        ascender = ascender.max(glyph_ascender * glyph.units);
+        descender = descender.min(glyph_descender * glyph.units);
+        line_gap = line_gap.max(glyph_line_gap * glyph.units);
+    }
+    if options.line_height == LineHeight::Bbox {
+        let ink = glyphs.iter().filter_map(|glyph| {
+            let bbox = glyph.face.glyph_bounding_box(glyph.id)?;
+            Some((
+                bbox.y_max as f32 * glyph.units,
+                bbox.y_min as f32 * glyph.units,
+            ))
+        });
+        // Lines without any ink keep the font's height.
+        let bounds =
+            ink.fold(None, |bounds: Option<(f32, f32)>, (top, bottom)| {
+                Some(match bounds {
+                    Some((max, min)) => (max.max(top), min.min(bottom)),
+                    None => (top, bottom),
+                })
+            });
+        if let Some((top, bottom)) = bounds {
+            ascender = top;
+            descender = bottom;
+        }
+    }
+    let height = ascender - descender + line_gap;
+    let em = face.units_per_em() as f32;
+    let mut advances = glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, glyph)| {
+            if centers_mark(*glyph, options) {
+                return 0.0;
+            }
+            if let Some(width) = blank_width(*glyph) {
+                return width * em * options.advance_scale
+                    + options.letter_spacing;
+            }
+            let kerning = match glyphs.get(i + 1) {
+                // Only pairs from the same face can kern.
+                Some(next) if std::ptr::eq(glyph.face, next.face) => {
+                    kerning(glyph.face, glyph.id, next.id, options)
+                        * glyph.units
+                        * options.advance_scale
+                }
+                _ => 0.0,
+            };
+            advance(*glyph, options) + kerning
+        })
+        .collect::<Vec<_>>();
+    // Tabs go to the next stop, counted from the start of the line.
+    let stop = options.tab_width as f32
+        * match face.glyph_index(' ') {
+            Some(id) => advance(lookup_in(face, id), options),
+            None => em / 4.0,
+        };
+    let mut pen = 0.0;
+    for (advance, glyph) in advances.iter_mut().zip(glyphs) {
+        if glyph.c == '\t' && stop > 0.0 {
+            *advance = stop - pen % stop;
+        }
+        pen += *advance;
+    }
+    let width: f32 = advances.iter().sum();
+    let scale = match options.size {
+        Some(size) => size / face.units_per_em() as f32,
+        None => 1.0,
+    };
+    let scale = match options.max_height {
+        Some(max_height) if height * scale > max_height => max_height / height,
+        _ => scale,
+    };
+    Layout {
+        advances,
+        ascender,
+        descender,
+        scale,
+        width: width * scale,
+        height: height * scale,
+    }
+}
+
+/// Makes a line wider than `--canvas-width` fit, or warns that it won't.
+fn fit_canvas(layout: &mut Layout, options: &RenderOptions) {
+    let canvas = match options.canvas_width {
+        Some(canvas) => canvas,
+        None => return,
+    };
+    let height = options.canvas_height.unwrap_or(layout.height);
+    let (across, down) = (canvas / layout.width, height / layout.height);
+    let factor = match options.canvas_fit {
+        Some(CanvasFit::Contain) => across.min(down),
+        Some(CanvasFit::Cover) => across.max(down),
+        None if layout.width <= canvas => return,
+        None if options.shrink_to_fit => across,
+        None => {
+            eprintln!(
+                "{}line is {} wide, more than the canvas width of {}, and \
+                 overflows.",
+                YELLOW_WARNING, layout.width, canvas
+            );
+            return;
+        }
+    };
+    // Lines with nothing to scale keep their size.
+    if factor.is_finite() && factor > 0.0 {
+        layout.scale *= factor;
+        layout.width *= factor;
+        layout.height *= factor;
+    }
+}
+
+/// How far right a line of the given width goes on a canvas that wide.
+fn align_shift(width: f32, canvas: f32, options: &RenderOptions) -> f32 {
+    match options.align {
+        Align::Left => 0.0,
+        Align::Center => (canvas - width) / 2.0,
+        Align::Right => canvas - width,
+    }
+}
+
+/// Padding that centers the content on the longer side.
+fn square_padding(
+    width: f32,
+    height: f32,
+    options: &RenderOptions,
+) -> (f32, f32) {
+    if options.fit_to_square {
+        let side = width.max(height);
+        ((side - width) / 2.0, (side - height) / 2.0)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// How far the glyph moves the pen on its own, kerning aside, scaled like
+/// the svg it would be drawn in.
+pub fn advance_width(
+    face: &ttf::Face,
+    glyph: Glyph,
+    options: &RenderOptions,
+) -> f32 {
+    layout(face, &[glyph], options).width
+}
+
+/// A glyph's outline and where it goes, for drawing on something other
+/// than an svg.
+pub struct PositionedGlyph {
+    pub id: ttf::GlyphId,
+    /// Svg path data with the glyph's origin at 0, 0 and y pointing down,
+    /// empty for glyphs without an outline.
+    pub path: String,
+    /// Where the origin goes, the left edge and baseline before any
+    /// padding.
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The glyphs of the text laid out like `render` would, one at a time.
+///
+/// ```
+/// use sentences2svg::{positioned_glyphs, RenderOptions};
+///
+/// let font = std::fs::read("fonts/DejaVuSans.ttf").unwrap();
+/// let faces = [ttf_parser::Face::from_slice(&font, 0).unwrap()];
+/// let options = RenderOptions::default();
+/// let glyphs: Vec<_> = positioned_glyphs(&faces, "hi", &options).collect();
+/// assert_eq!(glyphs.len(), 2);
+/// assert!(glyphs[0].x < glyphs[1].x);
+/// assert!(glyphs.iter().all(|glyph| !glyph.path.is_empty()));
+/// ```
+pub fn positioned_glyphs<'a>(
+    faces: &'a [ttf::Face<'a>],
+    text: &str,
+    options: &'a RenderOptions,
+) -> impl Iterator<Item = PositionedGlyph> + 'a {
+    let face = &faces[0];
+    let glyphs = glyphs(faces, text, options);
+    let layout = layout(face, &glyphs, options);
+    let scale = layout.scale;
+    let baseline_offset = options.baseline_offset + layout.ascender * scale;
+    let precision = precision(face, scale, options);
+    place(glyphs, layout, (0.0, baseline_offset), options).map(
+        move |placement| {
+            let glyph = placement.glyph;
+            let mut builder = Builder {
+                buffer: String::new(),
+                offset: 0.0,
+                baseline_offset: 0.0,
+                scale,
+                units: glyph.units,
+                round: options.crisp_edges,
+                precision,
+                compact: options.compact_paths.then(Compact::default),
+                flatten: options.flatten_tolerance.map(Flatten::new),
+                holes: None,
+            };
+            if outline(glyph, options, &mut builder).is_none() {
+                builder.buffer.clear();
+            }
+            PositionedGlyph {
+                id: glyph.id,
+                path: builder.buffer,
+                x: placement.offset * scale,
+                y: placement.baseline_offset,
+            }
+        },
+    )
+}
+
+/// How a line was laid out, in the primary face's units.
+pub struct LineMetrics {
+    pub width: f32,
+    pub height: f32,
+    pub ascent: f32,
+    /// Below the baseline, so usually negative.
+    pub descent: f32,
+    /// Turns the units into pixels.
+    pub scale: f32,
+    pub glyphs: Vec<GlyphMetrics>,
+}
+
+pub struct GlyphMetrics {
+    pub c: char,
+    pub id: ttf::GlyphId,
+    /// Where the glyph's origin is drawn, from the left edge.
+    pub x_offset: f32,
+    /// Kerning included.
+    pub advance: f32,
+}
+
+/// Measures the glyphs the way `render` lays them out.
+pub fn line_metrics(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+) -> LineMetrics {
+    let layout = layout(face, glyphs, options);
+    let scale = layout.scale;
+    let height = layout.height / scale;
+    let ascent = layout.ascender;
+    LineMetrics {
+        width: layout.width / scale,
+        height,
+        ascent,
+        descent: ascent - height,
+        scale,
+        glyphs: place(glyphs.to_vec(), layout, (0.0, 0.0), options)
+            .map(|placement| GlyphMetrics {
+                c: placement.glyph.c,
+                id: placement.glyph.id,
+                x_offset: placement.offset,
+                advance: placement.advance,
+            })
+            .collect(),
+    }
+}
+
+/// Width and height `render` would give the glyphs without a cell size or
+/// padding.
+pub fn canvas_size(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+) -> (f32, f32) {
+    let Layout { width, height, .. } = layout(face, glyphs, options);
+    let (pad_x, pad_y) = square_padding(width, height, options);
+    (width + pad_x * 2.0, height + pad_y * 2.0)
+}
+
+/// Width and height of the text in the face, measured without drawing any
+/// paths.
+///
+/// ```
+/// use sentences2svg::{measure_line, RenderOptions};
+///
+/// let font = std::fs::read("fonts/DejaVuSans.ttf").unwrap();
+/// let face = ttf_parser::Face::from_slice(&font, 0).unwrap();
+/// let options = RenderOptions::default();
+/// let (width, height) = measure_line(&face, "hello", &options);
+/// let (longer, _) = measure_line(&face, "hello world", &options);
+/// assert!(width > 0.0 && longer > width);
+/// assert_eq!(height, (face.ascender() - face.descender()) as f32);
+/// ```
+pub fn measure_line(
+    face: &ttf::Face,
+    text: &str,
+    options: &RenderOptions,
+) -> (f32, f32) {
+    let faces = std::slice::from_ref(face);
+    canvas_size(face, &glyphs(faces, text, options), options)
+}
+
+/// Lays the glyphs out in an svg, returned along with its width and height.
+/// The id is only used when the root is a `<symbol>`, the text with
+/// `embed_text`.
+pub fn render(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+    id: &str,
+    text: &str,
+) -> (String, (f32, f32)) {
+    render_titled(face, glyphs, options, id, text, None)
+}
+
+/// `render` with a `<title>` of its own instead of the text's.
+pub fn render_titled(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+    id: &str,
+    text: &str,
+    title: Option<&str>,
+) -> (String, (f32, f32)) {
+    if options.vertical {
+        return render_vertical(face, glyphs, options, id, text, title);
+    }
+    if options.max_width.is_some() {
+        let lines = wrap(face, glyphs, options);
+        if lines.len() > 1 {
+            return stack(face, &lines, &[], options, id, text, title);
+        }
+    }
+    let mut layout = layout(face, glyphs, options);
+    fit_canvas(&mut layout, options);
+    let (mut width, mut height) = (layout.width, layout.height);
+    // Where the ink starts left of the pen, and how far above the baseline.
+    let mut ink = (0.0, layout.ascender * layout.scale);
+    if options.tight_bounds {
+        // Lines of blanks keep the advances and line height.
+        if let Some((left, top, right, bottom)) =
+            ink_bounds(glyphs, &layout, options)
+        {
+            width = right - left;
+            height = bottom - top;
+            ink = (left, -top);
+        }
+    }
+    let (pad_x, pad_y) = match options.cell {
+        Some((cell_width, cell_height)) => {
+            ((cell_width - width) / 2.0, (cell_height - height) / 2.0)
+        }
+        None => square_padding(width, height, options),
+    };
+    let (pad_x, pad_y) = (pad_x + options.padding.0, pad_y + options.padding.1);
+    let mut baseline_offset = options.baseline_offset + ink.1 + pad_y;
+    let mut size = (width + pad_x * 2.0, height + pad_y * 2.0);
+    let mut left = pad_x - ink.0;
+    if let Some(canvas) = options.canvas_width {
+        size.0 = canvas + options.padding.0 * 2.0;
+        left = options.padding.0 + align_shift(width, canvas, options);
+    }
+    if let Some(canvas) = options.canvas_height {
+        // Centered, so contain leaves even bands and cover clips evenly.
+        size.1 = canvas + pad_y * 2.0;
+        baseline_offset += (canvas - height) / 2.0;
+    }
+    let mut w = start_document(options, id, text, title, size, baseline_offset);
+    let mut shared = Shared::default();
+    if options.use_defs {
+        shared.write_defs(&mut w, face, &[(glyphs, &layout)], options);
+    }
+    write_glyphs(
+        &mut w,
+        face,
+        glyphs,
+        &layout,
+        (left, baseline_offset),
+        options,
+        &mut shared,
+    );
+    (end_document(w, options), size)
+}
+
+/// Where the top of the glyph's vertical advance is, above the baseline, in
+/// its own face's units.
+fn vertical_origin(glyph: Glyph) -> f32 {
+    let Glyph { face, id, .. } = glyph;
+    let from_metrics = || {
+        let bearing = face.glyph_ver_side_bearing(id)?;
+        Some(face.glyph_bounding_box(id)?.y_max + bearing)
+    };
+    face.glyph_y_origin(id)
+        .or_else(from_metrics)
+        .or_else(|| face.vertical_ascender())
+        .unwrap_or(face.ascender()) as f32
+}
+
+/// A column as wide as the widest glyph, each glyph centered in it and a
+/// vertical advance below the last. Fonts without vertical metrics advance
+/// by their line height.
+fn render_vertical(
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    options: &RenderOptions,
+    id: &str,
+    text: &str,
+    title: Option<&str>,
+) -> (String, (f32, f32)) {
+    let scale = match options.size {
+        Some(size) => size / face.units_per_em() as f32,
+        None => 1.0,
+    };
+    let widths = glyphs
+        .iter()
+        .map(|glyph| {
+            let advance = glyph.face.glyph_hor_advance(glyph.id);
+            advance.unwrap_or_default() as f32 * glyph.units
+        })
+        .collect::<Vec<_>>();
+    let advances = glyphs
+        .iter()
+        .map(|glyph| {
+            let face = glyph.face;
+            let advance = face.glyph_ver_advance(glyph.id).map_or_else(
+                || face.height() as f32 + face.line_gap() as f32,
+                f32::from,
+            );
+            advance * glyph.units * options.advance_scale
+                + options.letter_spacing
+        })
+        .collect::<Vec<_>>();
+    let width = widths.iter().copied().fold(0.0f32, f32::max);
+    let height: f32 = advances.iter().sum();
+    let (pad_x, pad_y) = options.padding;
+    let size = (width * scale + pad_x * 2.0, height * scale + pad_y * 2.0);
+    let mut w = start_document(
+        options,
+        id,
+        text,
+        title,
+        size,
+        options.baseline_offset + pad_y,
+    );
+    let precision = precision(face, scale, options);
+    let mut merged = options.merge_paths.then(Merged::default);
+    let mut pen = 0.0;
+    for (index, ((glyph, glyph_width), advance)) in
+        glyphs.iter().zip(&widths).zip(&advances).enumerate()
+    {
+        if options.annotate_glyphs {
+            w.start_element("g");
+            w.write_attribute(
+                "data-char",
+                &escape_xml(&glyph.c.to_string(), true),
+            );
+            w.write_attribute("data-index", &index);
+            w.write_attribute("data-cluster", &index);
+        }
+        let mut builder = Builder {
+            buffer: String::new(),
+            offset: pad_x / scale + (width - glyph_width) / 2.0,
+            baseline_offset: options.baseline_offset
+                + pad_y
+                + (pen + vertical_origin(*glyph) * glyph.units) * scale,
+            scale,
+            units: glyph.units,
+            round: options.crisp_edges,
+            precision,
+            compact: Merged::compact(&mut merged, options),
+            flatten: options.flatten_tolerance.map(Flatten::new),
+            holes: None,
+        };
+        let drawn = outline(*glyph, options, &mut builder).is_some();
+        match &mut merged {
+            Some(merged) => merged.push(builder),
+            None if drawn => {
+                w.start_element("path");
+                w.write_attribute("d", &builder.buffer);
+                w.end_element();
+            }
+            None => {}
+        }
+        if options.annotate_glyphs {
+            w.end_element();
+        }
+        pen += advance;
+    }
+    if let Some(merged) = merged {
+        merged.write(&mut w);
+    }
+    (end_document(w, options), size)
+}
+
+/// Breaks the glyphs into lines no wider than `max_width`, at spaces where
+/// it can and between glyphs when a word doesn't fit on its own. Spaces at
+/// a break are dropped.
+fn wrap<'a>(
+    face: &ttf::Face,
+    glyphs: &[Glyph<'a>],
+    options: &RenderOptions,
+) -> Vec<Vec<Glyph<'a>>> {
+    let max_width = match options.max_width {
+        Some(max_width) => max_width,
+        None => return vec![glyphs.to_vec()],
+    };
+    // Measured whole, so kerning across the break doesn't count.
+    let fits =
+        |glyphs: &[Glyph]| layout(face, glyphs, options).width <= max_width;
+    let mut lines = vec![];
+    let mut line: Vec<Glyph> = vec![];
+    let mut rest = glyphs;
+    while !rest.is_empty() {
+        let spaces = rest.iter().take_while(|g| g.c.is_whitespace()).count();
+        let word = rest[spaces..]
+            .iter()
+            .take_while(|g| !g.c.is_whitespace())
+            .count();
+        let (spaces, word, next) = (
+            &rest[..spaces],
+            &rest[spaces..spaces + word],
+            &rest[spaces + word..],
+        );
+        rest = next;
+        let candidate = if line.is_empty() && !lines.is_empty() {
+            word.to_vec()
+        } else {
+            [line.as_slice(), spaces, word].concat()
+        };
+        if fits(&candidate) {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        if fits(word) {
+            line = word.to_vec();
+            continue;
+        }
+        for &glyph in word {
+            line.push(glyph);
+            if line.len() > 1 && !fits(&line) {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, vec![glyph]));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Stacks every line in one svg, each a line height below the last, as wide
+/// as the widest. Lines without glyphs still take up their line, and ones
+/// wider than `max_width` wrap. With `use_defs` the lines share one
+/// `<defs>`. `numbers` go in the `line_class` classes, parts of a wrapped
+/// line sharing theirs.
+///
+/// ```
+/// use sentences2svg::render::{glyphs, render_document};
+/// use sentences2svg::RenderOptions;
+///
+/// let font = std::fs::read("fonts/DejaVuSans.ttf").unwrap();
+/// let faces = [ttf_parser::Face::from_slice(&font, 0).unwrap()];
+/// let options = RenderOptions {
+///     use_defs: true,
+///     ..Default::default()
+/// };
+/// let lines = [
+///     glyphs(&faces, "hello", &options),
+///     glyphs(&faces, "yellow", &options),
+/// ];
+/// let (svg, _) = render_document(&faces[0], &lines, &[], &options, "");
+/// assert_eq!(svg.matches("<defs>").count(), 1);
+/// // h, e, l, o, y and w, however many lines use them.
+/// assert_eq!(svg.matches("<path id=").count(), 6);
+/// assert_eq!(svg.matches("<use ").count(), 11);
+/// ```
+pub fn render_document(
+    face: &ttf::Face,
+    lines: &[Vec<Glyph>],
+    numbers: &[usize],
+    options: &RenderOptions,
+    text: &str,
+) -> (String, (f32, f32)) {
+    let (lines, numbers): (Vec<_>, Vec<_>) = lines
+        .iter()
+        .enumerate()
+        .flat_map(|(i, glyphs)| {
+            let number = numbers.get(i).copied();
+            let lines = wrap(face, glyphs, options);
+            lines.into_iter().map(move |line| (line, number))
+        })
+        .unzip();
+    stack(face, &lines, &numbers, options, "document", text, None)
+}
+
+fn stack(
+    face: &ttf::Face,
+    lines: &[Vec<Glyph>],
+    numbers: &[Option<usize>],
+    options: &RenderOptions,
+    id: &str,
+    text: &str,
+    title: Option<&str>,
+) -> (String, (f32, f32)) {
+    let layouts = lines
+        .iter()
+        .map(|glyphs| {
+            let mut layout = layout(face, glyphs, options);
+            fit_canvas(&mut layout, options);
+            layout
+        })
+        .collect::<Vec<_>>();
+    let (pad_x, pad_y) = options.padding;
+    // From the top of the first line. Each baseline is the last one's
+    // descent and line gap below it plus the next line's ascent, times the
+    // factor.
+    let ascent = |layout: &Layout| layout.ascender * layout.scale;
+    let gap = |layout: &Layout| layout.height - ascent(layout);
+    let descent = |layout: &Layout| -layout.descender * layout.scale;
+    let mut baselines: Vec<f32> = vec![];
+    let mut bottom = layouts.last().map(descent);
+    if options.baseline_grid {
+        // Every line measured like the tallest, so the baselines are evenly
+        // spaced whatever each line's glyphs.
+        let tallest = |metric: &dyn Fn(&Layout) -> f32| {
+            layouts.iter().map(metric).fold(0.0, f32::max)
+        };
+        let (ascent, gap) = (tallest(&ascent), tallest(&gap));
+        let pitch = (gap + ascent) * options.line_height_factor;
+        baselines = (0..layouts.len())
+            .map(|i| ascent + pitch * i as f32)
+            .collect();
+        bottom = bottom.map(|_| tallest(&descent));
+    } else {
+        for (i, layout) in layouts.iter().enumerate() {
+            baselines.push(match i {
+                0 => ascent(layout),
+                _ => {
+                    let above = gap(&layouts[i - 1]) + ascent(layout);
+                    baselines[i - 1] + above * options.line_height_factor
+                }
+            });
+        }
+    }
+    let width = layouts
+        .iter()
+        .map(|layout| layout.width)
+        .fold(0.0, f32::max);
+    // The last line ends at its descender, without a gap under it.
+    let height = match (bottom, baselines.last()) {
+        (Some(descent), Some(baseline)) => baseline + descent,
+        _ => 0.0,
+    };
+    let canvas = options.canvas_width.unwrap_or(width);
+    let size = (canvas + pad_x * 2.0, height + pad_y * 2.0);
+    let first_baseline = baselines.first().copied().unwrap_or_default();
+    let mut w = start_document(
+        options,
+        id,
+        text,
+        title,
+        size,
+        options.baseline_offset + pad_y + first_baseline,
+    );
+    let mut shared = Shared::default();
+    if options.use_defs {
+        let lines = lines
+            .iter()
+            .map(Vec::as_slice)
+            .zip(&layouts)
+            .collect::<Vec<_>>();
+        shared.write_defs(&mut w, face, &lines, options);
+    }
+    for (i, ((glyphs, layout), baseline)) in
+        lines.iter().zip(&layouts).zip(baselines).enumerate()
+    {
+        let baseline_offset = options.baseline_offset + pad_y + baseline;
+        let class = match (&options.line_class, numbers.get(i)) {
+            (Some(prefix), Some(Some(number))) => Some((prefix, number)),
+            _ => None,
+        };
+        let grouped = options.emit_baseline || class.is_some();
+        if grouped {
+            w.start_element("g");
+        }
+        if let Some((prefix, number)) = class {
+            w.write_attribute_fmt(
+                "class",
+                format_args!("{} {}-{}", prefix, prefix, number),
+            );
+        }
+        if options.emit_baseline {
+            w.write_attribute("data-baseline", &baseline_offset);
+        }
+        write_glyphs(
+            &mut w,
+            face,
+            glyphs,
+            layout,
+            (
+                pad_x + align_shift(layout.width, canvas, options),
+                baseline_offset,
+            ),
+            options,
+            &mut shared,
+        );
+        if grouped {
+            w.end_element();
+        }
+    }
+    (end_document(w, options), size)
+}
+
+/// Opens the root element and writes everything that goes before the
+/// glyphs. The title is the text's with `embed_text` unless given.
+fn start_document(
+    options: &RenderOptions,
+    id: &str,
+    text: &str,
+    title: Option<&str>,
+    size: (f32, f32),
+    baseline_offset: f32,
+) -> XmlWriter {
+    let mut w = XmlWriter::new(Options {
+        use_single_quote: !options.double_quotes,
+        indent: options.indent,
+        ..Default::default()
+    });
+    if let Some(comment) = &options.comment {
+        w.write_comment_fmt(format_args!(" {} ", comment));
+    }
+    w.start_element(match options.root_tag {
+        RootTag::Svg => "svg",
+        RootTag::Symbol => "symbol",
+        RootTag::G => "g",
+    });
+    w.write_attribute("xmlns", "http://www.w3.org/2000/svg");
+    if options.crisp_edges {
+        w.write_attribute("shape-rendering", "crispEdges");
+    }
+    if options.root_tag == RootTag::Symbol {
+        w.write_attribute("id", id);
+    }
+    if let Some(version) = &options.svg_version {
+        if options.root_tag == RootTag::Svg {
+            w.write_attribute("version", version);
+        }
+    }
+    match options.root_tag {
+        RootTag::Svg => {
+            w.write_attribute("width", &size.0);
+            w.write_attribute("height", &size.1);
+            w.write_attribute_fmt(
+                "viewBox",
+                format_args!("0 0 {} {}", size.0, size.1),
+            );
+        }
+        RootTag::Symbol => w.write_attribute_fmt(
+            "viewBox",
+            format_args!("0 0 {} {}", size.0, size.1),
+        ),
+        // A group has no size of its own.
+        RootTag::G => {}
+    }
+    if options.emit_baseline {
+        // Font units put the baseline at y = 0, which the builder maps to
+        // the ascender plus any offset and padding, whatever the scale.
+        w.write_attribute("data-baseline", &baseline_offset);
+    }
+    if options.embed_text {
+        w.write_attribute("role", "img");
+        w.write_attribute("aria-label", &escape_xml(text, true));
+    }
+    if let Some(title) = title.or(options.embed_text.then_some(text)) {
+        w.start_element("title");
+        w.set_preserve_whitespaces(true);
+        w.write_text(&escape_xml(title, false));
+        w.end_element();
+        w.set_preserve_whitespaces(false);
+    }
+    if let Some(metadata) = &options.metadata {
+        w.start_element("metadata");
+        w.start_element("generator");
+        w.write_attribute("name", env!("CARGO_PKG_NAME"));
+        w.write_attribute("version", env!("CARGO_PKG_VERSION"));
+        w.end_element();
+        w.start_element("font");
+        w.write_attribute("family", &escape_xml(&metadata.font_family, true));
+        w.end_element();
+        if options.embed_text {
+            w.start_element("text");
+            w.set_preserve_whitespaces(true);
+            w.write_text(&escape_xml(text, false));
+            w.end_element();
+            w.set_preserve_whitespaces(false);
+        }
+        w.start_element("created");
+        w.write_attribute("timestamp", &metadata.created);
+        w.end_element();
+        w.end_element();
+    }
+    if let Some(background) = &options.background {
+        w.start_element("rect");
+        w.write_attribute("width", "100%");
+        w.write_attribute("height", "100%");
+        w.write_attribute("fill", background);
+        w.end_element();
+    }
+    if let Some(transform) = &options.transform {
+        w.start_element("g");
+        w.write_attribute("transform", transform);
+    }
+    if styled(options) {
+        w.start_element("g");
+        if let Some(fill) = &options.fill {
+            w.write_attribute("fill", fill);
+        }
+        if let Some(stroke) = &options.stroke {
+            w.write_attribute("stroke", stroke);
+        }
+        if let Some(stroke_width) = &options.stroke_width {
+            w.write_attribute("stroke-width", stroke_width);
+        }
+        if let Some(linejoin) = &options.stroke_linejoin {
+            w.write_attribute("stroke-linejoin", linejoin);
+        }
+        if let Some(linecap) = &options.stroke_linecap {
+            w.write_attribute("stroke-linecap", linecap);
+        }
+    }
+    w
+}
+
+/// Escapes what xmlwriter leaves alone: it only does the quote in
+/// attributes and `<` in text. Control characters xml can't hold at all
+/// are dropped.
+fn escape_xml(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' if attribute => escaped.push_str("&lt;"),
+            // Attributes would have them normalized to spaces.
+            '\t' | '\n' | '\r' if attribute => {
+                write!(escaped, "&#{};", c as u32).unwrap()
+            }
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether the paths get wrapped in a group setting their style.
+fn styled(options: &RenderOptions) -> bool {
+    options.fill.is_some()
+        || options.stroke.is_some()
+        || options.stroke_width.is_some()
+        || options.stroke_linejoin.is_some()
+        || options.stroke_linecap.is_some()
+}
+
+fn end_document(mut w: XmlWriter, options: &RenderOptions) -> String {
+    if styled(options) {
+        w.end_element();
+    }
+    if options.transform.is_some() {
+        w.end_element();
+    }
+    w.end_element();
+    let svg = w.end_document();
+    if !options.xml_declaration {
+        return svg;
+    }
+    // xmlwriter's own declaration says standalone="no" too.
+    let newline = if options.indent == Indent::None {
+        ""
+    } else {
+        "\n"
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}{}",
+        newline, svg
+    )
+}
+
+/// Big renders don't need decimals, small ones need a few to keep their
+/// shape: three at one pixel per em, none from a thousand upwards.
+fn precision(
+    face: &ttf::Face,
+    scale: f32,
+    options: &RenderOptions,
+) -> Option<i32> {
+    if options.precision.is_some() {
+        options.precision
+    } else if options.adaptive_precision {
+        let em = face.units_per_em() as f32 * scale;
+        Some((3 - em.log10().floor() as i32).clamp(0, 4))
+    } else {
+        None
+    }
+}
+
+/// Outlines written once and referenced afterwards, shared by every line of
+/// a document.
+#[derive(Default)]
+struct Shared<'a> {
+    /// Path data drawn at x = 0 and the offset it was first used at, for
+    /// `--dedupe-paths`.
+    drawn: Vec<(String, f32)>,
+    /// Each glyph in `<defs>` with the scale it was outlined at, and its id
+    /// unless it has no outline, for `--use-defs`.
+    defs: Vec<(Glyph<'a>, f32, Option<String>)>,
+}
+
+impl<'a> Shared<'a> {
+    fn def(
+        &self,
+        glyph: Glyph,
+        scale: f32,
+    ) -> Option<&(Glyph<'a>, f32, Option<String>)> {
+        self.defs.iter().find(|(def, def_scale, _)| {
+            std::ptr::eq(def.face, glyph.face)
+                && def.id == glyph.id
+                && def.units == glyph.units
+                && *def_scale == scale
+        })
+    }
+
+    /// Outlines every distinct glyph at x = 0 on the baseline, named after
+    /// its glyph id.
+    fn write_defs(
+        &mut self,
+        w: &mut XmlWriter,
+        face: &ttf::Face,
+        lines: &[(&[Glyph<'a>], &Layout)],
+        options: &RenderOptions,
+    ) {
+        w.start_element("defs");
+        for (glyphs, layout) in lines {
+            for &glyph in glyphs.iter() {
+                if self.def(glyph, layout.scale).is_some() {
+                    continue;
+                }
+                let mut builder = Builder {
+                    buffer: String::new(),
+                    offset: 0.0,
+                    baseline_offset: 0.0,
+                    scale: layout.scale,
+                    units: glyph.units,
+                    round: options.crisp_edges,
+                    precision: precision(face, layout.scale, options),
+                    compact: options.compact_paths.then(Compact::default),
+                    flatten: options.flatten_tolerance.map(Flatten::new),
+                    holes: None,
+                };
+                let id = outline(glyph, options, &mut builder).map(|_| {
+                    // Fallback faces and other scales can reuse an id.
+                    match self
+                        .defs
+                        .iter()
+                        .filter(|(def, _, id)| {
+                            def.id == glyph.id && id.is_some()
+                        })
+                        .count()
+                    {
+                        0 => format!("g{}", glyph.id.0),
+                        n => format!("g{}-{}", glyph.id.0, n),
+                    }
+                });
+                if let Some(id) = &id {
+                    w.start_element("path");
+                    w.write_attribute("id", id);
+                    w.write_attribute("d", &builder.buffer);
+                    w.end_element();
+                }
+                self.defs.push((glyph, layout.scale, id));
+            }
+        }
+        w.end_element();
+    }
+}
+
+/// How far the glyph is moved right and up from where the font puts it, in
+/// the primary face's units.
+fn origin_shift(
+    glyph: Glyph,
+    advance: f32,
+    layout: &Layout,
+    options: &RenderOptions,
+) -> (f32, f32) {
+    let bbox = match glyph.face.glyph_bounding_box(glyph.id) {
+        Some(bbox) if options.glyph_origin == GlyphOrigin::Center => bbox,
+        _ => return (0.0, 0.0),
+    };
+    let center_x = (bbox.x_min as f32 + bbox.x_max as f32) / 2.0;
+    let center_y = (bbox.y_min as f32 + bbox.y_max as f32) / 2.0;
+    let line_middle = layout.ascender - layout.height / layout.scale / 2.0;
+    (
+        advance / 2.0 - center_x * glyph.units,
+        line_middle - center_y * glyph.units,
+    )
+}
+
+/// The union of the glyphs' outlines' bounds as `(left, top, right,
+/// bottom)`, scaled, from a pen starting at 0 on a baseline at 0. `None`
+/// when no glyph has any ink.
+fn ink_bounds(
+    glyphs: &[Glyph],
+    layout: &Layout,
+    options: &RenderOptions,
+) -> Option<(f32, f32, f32, f32)> {
+    let scale = layout.scale;
+    place(glyphs.to_vec(), layout.clone(), (0.0, 0.0), options)
+        .filter(|placement| blank_width(placement.glyph).is_none())
+        .filter_map(|placement| {
+            let Placement {
+                glyph,
+                offset,
+                baseline_offset,
+                ..
+            } = placement;
+            let outline = cached_outline(glyph, options);
+            let (x_min, y_min, x_max, y_max) =
+                outline.as_ref().as_ref()?.bounds()?;
+            // Emboldened glyphs are moved right like `outline` does.
+            let offset = offset + options.embolden / 2.0;
+            let x = |x: f32| (x * glyph.units + offset) * scale;
+            let y = |y: f32| baseline_offset - y * glyph.units * scale;
+            Some((x(x_min), y(y_max), x(x_max), y(y_min)))
+        })
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+}
+
+/// Where one glyph of a line is drawn.
+struct Placement<'a> {
+    glyph: Glyph<'a>,
+    /// The pen before the glyph, in the primary face's units.
+    pen: f32,
+    advance: f32,
+    /// The pen moved by the glyph origin, in the primary face's units.
+    offset: f32,
+    baseline_offset: f32,
+}
+
+/// Walks the pen along the glyphs from the given left edge and baseline.
+fn place<'a: 'o, 'o>(
+    glyphs: Vec<Glyph<'a>>,
+    layout: Layout,
+    (left, baseline_offset): (f32, f32),
+    options: &'o RenderOptions,
+) -> impl Iterator<Item = Placement<'a>> + 'o {
+    let advances = layout.advances.clone();
+    glyphs.into_iter().zip(advances).scan(
+        (left / layout.scale, None),
+        move |(pen, previous), (glyph, advance)| {
+            let (shift_x, shift_y) =
+                origin_shift(glyph, advance, &layout, options);
+            let mut offset = *pen + shift_x;
+            // Without mark positioning from the font, a leftover combining
+            // mark is centered over the glyph before it.
+            let bbox = glyph.face.glyph_bounding_box(glyph.id);
+            if let (true, Some((base_pen, base_advance)), Some(bbox)) =
+                (centers_mark(glyph, options), *previous, bbox)
+            {
+                let center = (bbox.x_min as f32 + bbox.x_max as f32) / 2.0;
+                offset = base_pen + base_advance / 2.0 - center * glyph.units;
+            }
+            let placement = Placement {
+                glyph,
+                pen: *pen,
+                advance,
+                offset,
+                baseline_offset: baseline_offset - shift_y * layout.scale,
+            };
+            if !centers_mark(glyph, options) {
+                *previous = Some((*pen, advance));
+            }
+            *pen += advance;
+            Some(placement)
+        },
+    )
+}
+
+/// Marks a space or tab in the cell its advance takes up, given as its
+/// left edge and width, a bit above the baseline.
+fn write_whitespace(
+    w: &mut XmlWriter,
+    face: &ttf::Face,
+    c: char,
+    (left, width): (f32, f32),
+    baseline_offset: f32,
+    scale: f32,
+) {
+    let em = face.units_per_em() as f32 * scale;
+    let y = baseline_offset - em * 0.3;
+    match c {
+        ' ' => {
+            w.start_element("circle");
+            w.write_attribute("cx", &(left + width / 2.0));
+            w.write_attribute("cy", &y);
+            w.write_attribute("r", &(em * 0.06));
+            w.write_attribute("fill", "#bbbbbb");
+            w.end_element();
+        }
+        '\t' => {
+            let (start, end) = (left + width * 0.15, left + width * 0.85);
+            let head = (em * 0.12).min(end - start);
+            w.start_element("path");
+            w.write_attribute_fmt(
+                "d",
+                format_args!(
+                    "M {} {} L {} {} M {} {} L {} {} L {} {}",
+                    start,
+                    y,
+                    end,
+                    y,
+                    end - head,
+                    y - head,
+                    end,
+                    y,
+                    end - head,
+                    y + head
+                ),
+            );
+            w.write_attribute("fill", "none");
+            w.write_attribute("stroke", "#bbbbbb");
+            w.write_attribute("stroke-width", &(em * 0.04));
+            w.end_element();
+        }
+        _ => {}
+    }
+}
+
+/// A line's outlines put together for `merge_paths`, compact commands
+/// carrying on from one glyph to the next.
+#[derive(Default)]
+struct Merged {
+    d: String,
+    compact: Option<Compact>,
+}
+
+impl Merged {
+    /// The compact state for the next glyph's builder, carried on from the
+    /// last one when merging.
+    fn compact(
+        merged: &mut Option<Merged>,
+        options: &RenderOptions,
+    ) -> Option<Compact> {
+        merged
+            .as_mut()
+            .and_then(|merged| merged.compact.take())
+            .or_else(|| options.compact_paths.then(Compact::default))
+    }
+
+    fn push(&mut self, builder: Builder) {
+        self.d.push_str(&builder.buffer);
+        self.compact = builder.compact;
+    }
+
+    /// The fill rule is set, as overlapping glyphs in one path could
+    /// otherwise be drawn evenodd by whatever reads it.
+    fn write(self, w: &mut XmlWriter) {
+        if !self.d.is_empty() {
+            w.start_element("path");
+            w.write_attribute("fill-rule", "nonzero");
+            w.write_attribute("d", &self.d);
+            w.end_element();
+        }
+    }
+}
+
+/// Writes a path for each glyph with an outline, starting from the given
+/// left edge and baseline.
+fn write_glyphs(
+    w: &mut XmlWriter,
+    face: &ttf::Face,
+    glyphs: &[Glyph],
+    layout: &Layout,
+    (left, baseline_offset): (f32, f32),
+    options: &RenderOptions,
+    shared: &mut Shared,
+) {
+    let scale = layout.scale;
+    let precision = precision(face, scale, options);
+    let snap = |x: f32| if options.crisp_edges { x.round() } else { x };
+    let placements = place(
+        glyphs.to_vec(),
+        layout.clone(),
+        (left, baseline_offset),
+        options,
+    );
+    let mut merged = options.merge_paths.then(Merged::default);
+    for (
+        index,
+        Placement {
+            glyph,
+            pen,
+            advance,
+            offset,
+            baseline_offset,
+        },
+    ) in placements.enumerate()
+    {
+        let annotated = options.annotate_glyphs;
+        if annotated {
+            // One group per character, outline or not, so they line up
+            // with the text. Without shaping the cluster is the index.
+            w.start_element("g");
+            w.write_attribute(
+                "data-char",
+                &escape_xml(&glyph.c.to_string(), true),
+            );
+            w.write_attribute("data-index", &index);
+            w.write_attribute("data-cluster", &index);
+        }
+        'glyph: {
+            if options.whitespace_visible {
+                let cell = (pen * scale, advance * scale);
+                write_whitespace(
+                    w,
+                    face,
+                    glyph.c,
+                    cell,
+                    baseline_offset,
+                    scale,
+                );
+            }
+            if blank_width(glyph).is_some() {
+                break 'glyph;
+            }
+            // In mono the glyph's own outline stands in for its layers, or
+            // the layers all in one path where it has none.
+            let mono = options.mono_color_glyphs;
+            let layers = color::layers(glyph.face, glyph.id, options.palette)
+                .filter(|_| !mono || cached_outline(glyph, options).is_none());
+            if let Some(layers) = layers {
+                // Drawn in place each time, defs and dedupe only know the
+                // plain outline.
+                let new_builder = || Builder {
+                    buffer: String::new(),
+                    offset,
+                    baseline_offset,
+                    scale,
+                    units: glyph.units,
+                    round: options.crisp_edges,
+                    precision,
+                    compact: options.compact_paths.then(Compact::default),
+                    flatten: options.flatten_tolerance.map(Flatten::new),
+                    holes: None,
+                };
+                let mut merged = new_builder();
+                for (id, fill) in layers {
+                    let layer = Glyph { id, ..glyph };
+                    if mono {
+                        outline(layer, options, &mut merged);
+                        continue;
+                    }
+                    let mut builder = new_builder();
+                    if outline(layer, options, &mut builder).is_some() {
+                        w.start_element("path");
+                        if let Some(fill) = &fill {
+                            w.write_attribute("fill", fill);
+                        }
+                        w.write_attribute("d", &builder.buffer);
+                        w.end_element();
+                    }
+                }
+                if !merged.buffer.is_empty() {
+                    w.start_element("path");
+                    w.write_attribute("d", &merged.buffer);
+                    w.end_element();
+                }
+                break 'glyph;
+            }
+            if options.use_defs {
+                if let Some((_, _, Some(id))) = shared.def(glyph, scale) {
+                    w.start_element("use");
+                    w.write_attribute_fmt("href", format_args!("#{}", id));
+                    w.write_attribute("x", &snap(offset * scale));
+                    w.write_attribute("y", &snap(baseline_offset));
+                    w.end_element();
+                }
+                break 'glyph;
+            }
+            let mut builder = Builder {
+                buffer: String::new(),
+                offset: if options.dedupe_paths { 0.0 } else { offset },
+                baseline_offset,
+                scale,
+                units: glyph.units,
+                round: options.crisp_edges,
+                precision,
+                compact: Merged::compact(&mut merged, options),
+                flatten: options.flatten_tolerance.map(Flatten::new),
+                holes: None,
+            };
+            let drawn = outline(glyph, options, &mut builder).is_some();
+            if let Some(merged) = &mut merged {
+                merged.push(builder);
+                break 'glyph;
+            }
+            if !drawn {
+                break 'glyph;
+            }
+            let path: &str = &builder.buffer;
+            if !options.dedupe_paths {
+                w.start_element("path");
+                w.write_attribute("d", path);
+                w.end_element();
+            } else if let Some(index) =
+                shared.drawn.iter().position(|(drawn, _)| drawn == path)
+            {
+                w.start_element("use");
+                w.write_attribute_fmt("href", format_args!("#p{}", index));
+                w.write_attribute(
+                    "x",
+                    &(snap(offset * scale)
+                        - snap(shared.drawn[index].1 * scale)),
+                );
+                w.end_element();
+            } else {
+                w.start_element("path");
+                w.write_attribute_fmt(
+                    "id",
+                    format_args!("p{}", shared.drawn.len()),
+                );
+                w.write_attribute_fmt(
+                    "transform",
+                    format_args!("translate({} 0)", snap(offset * scale)),
+                );
+                w.write_attribute("d", path);
+                w.end_element();
+                shared.drawn.push((builder.buffer, offset));
+            }
+        }
+        if annotated {
+            w.end_element();
+        }
+    }
+    if let Some(merged) = merged {
+        merged.write(w);
+    }
+    // After the glyphs, so they're drawn on top.
+    let em = face.units_per_em() as f32;
+    let rules = [
+        (options.underline, face.underline_metrics(), (-0.1, 0.05)),
+        (options.strikethrough, face.strikeout_metrics(), (0.3, 0.05)),
+    ];
+    for (enabled, metrics, (position, thickness)) in rules {
+        if !enabled {
+            continue;
+        }
+        let (position, thickness) = match metrics {
+            Some(metrics) => {
+                (metrics.position as f32, metrics.thickness as f32)
+            }
+            None => (position * em, thickness * em),
+        };
+        // The position is the top of the rule.
+        w.start_element("rect");
+        w.write_attribute("x", &left);
+        w.write_attribute("y", &(baseline_offset - position * scale));
+        w.write_attribute("width", &layout.width);
+        w.write_attribute("height", &(thickness * scale));
+        w.end_element();
+    }
+}
@@ -0,0 +1,120 @@
+//! Svgs the rendered text is dropped into, in place of the element with the
+//! anchor's id.
+
+use crate::raster::{length, parse};
+
+/// A template checked before anything is rendered, so a bad one fails
+/// before the first line.
+pub struct Template {
+    text: String,
+    /// Byte range of the anchor, which the text replaces.
+    anchor: (usize, usize),
+    /// The tag the text goes in, a `<g>` anchor keeping its own.
+    open: String,
+    /// Where the anchor puts the text.
+    at: (f64, f64),
+    /// What the text is scaled to fit in, with --fit-anchor.
+    fit: Option<(Option<f64>, Option<f64>)>,
+}
+
+impl Template {
+    pub fn new(text: String, anchor: &str, fit: bool) -> Result<Self, String> {
+        let nodes = parse(&text)?;
+        let node = nodes
+            .iter()
+            .find(|node| node.get("id") == Some(anchor))
+            .ok_or_else(|| format!("no element has the id {}", anchor))?;
+        let (start, children, end) = node.span;
+        let number =
+            |name| node.get(name).map(|value| length(Some(value), 0.0));
+        let size = (number("width"), number("height"));
+        if fit && size == (None, None) {
+            return Err(format!(
+                "--fit-anchor needs the {} element to have a width or height",
+                anchor
+            ));
+        }
+        let open = if node.name == "g" {
+            let tag = &text[start..children];
+            match tag.strip_suffix("/>") {
+                Some(tag) => format!("{}>", tag),
+                None => tag.to_string(),
+            }
+        } else {
+            let id = anchor
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('"', "&quot;");
+            format!("<g id=\"{}\">", id)
+        };
+        Ok(Template {
+            anchor: (start, end),
+            open,
+            at: (number("x").unwrap_or(0.0), number("y").unwrap_or(0.0)),
+            fit: fit.then_some(size),
+            text,
+        })
+    }
+
+    /// The template with the children of the rendered svg in the anchor's
+    /// place, the rest of the markup left as it is.
+    pub fn apply(&self, svg: &str) -> String {
+        // The root may be any of the `--svg-root-tag` elements, and come
+        // after an `--xml-declaration` or `--svg-comment`.
+        let root = svg
+            .match_indices('<')
+            .find(|&(i, _)| !svg[i + 1..].starts_with(['!', '?']))
+            .map_or(0, |(i, _)| i);
+        let root_end = root + svg[root..].find('>').unwrap_or(0);
+        let (root_tag, children) = match svg[..root_end].strip_suffix('/') {
+            Some(tag) => (&svg[root..tag.len()], ""),
+            None => (
+                &svg[root..root_end],
+                svg.rfind("</")
+                    .map_or("", |close| &svg[root_end + 1..close]),
+            ),
+        };
+        let mut transform = String::new();
+        if self.at != (0.0, 0.0) {
+            transform = format!("translate({} {})", self.at.0, self.at.1);
+        }
+        if let Some((width, height)) = self.fit {
+            let tag = format!("{}/>", root_tag.trim_end_matches('/'));
+            let rendered = parse(&tag).ok();
+            let size = |name| {
+                let root = rendered.as_ref()?.first()?;
+                Some(length(root.get(name), 0.0)).filter(|&size| size > 0.0)
+            };
+            let ratios = [(width, size("width")), (height, size("height"))];
+            let scale = ratios
+                .iter()
+                .filter_map(|&(fit, size)| Some(fit? / size?))
+                .fold(f64::INFINITY, f64::min);
+            if scale.is_finite() {
+                if !transform.is_empty() {
+                    transform.push(' ');
+                }
+                transform.push_str(&format!("scale({})", scale));
+            }
+        }
+        let (start, end) = self.anchor;
+        if transform.is_empty() {
+            format!(
+                "{}{}{}</g>{}",
+                &self.text[..start],
+                self.open,
+                children,
+                &self.text[end..]
+            )
+        } else {
+            format!(
+                "{}{}<g transform=\"{}\">{}</g></g>{}",
+                &self.text[..start],
+                self.open,
+                transform,
+                children,
+                &self.text[end..]
+            )
+        }
+    }
+}